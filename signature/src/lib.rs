@@ -234,6 +234,7 @@ fn simple_type_dump_list(simple_type: &str) -> &str {
         "String" => "dump_string",
         "bool" => "dump_bool",
         "i128" => "dump_integer",
+        "usize" => "dump_usize",
         "ValueType" => "dump_type",
         "f64" => "dump_float",
         "Value" => "dump_value",
@@ -476,6 +477,8 @@ fn type_to_value(
                     mappings: quote! { (Some(#name_literal), #value_type) => #name = Some(#mutator), },
                     unnamed_mutate: Some(quote_spanned! { ty.span() =>
                     if #name.is_none() {
+                        // The last arm is unreachable for Option<Value>, which takes any value
+                        #[allow(unreachable_patterns)]
                         match _unnamed.pop_front() {
                             None => {}
                             Some(#value_type) => #name = Some(#mutator),
@@ -500,6 +503,7 @@ struct Metadata {
     long_description: Vec<String>,
     example: Option<String>,
     output: Option<TokenStream>,
+    input: Option<TokenStream>,
     #[allow(unused)]
     condition: bool,
 }
@@ -531,6 +535,7 @@ fn parse_metadata(metadata: TokenStream) -> SignatureResult<Metadata> {
     let mut short_description = None;
     let mut long_description = Vec::new();
     let mut output: Option<TokenStream> = None;
+    let mut input: Option<TokenStream> = None;
     let mut condition = false;
 
     let location = metadata.span().clone();
@@ -572,6 +577,12 @@ fn parse_metadata(metadata: TokenStream) -> SignatureResult<Metadata> {
                     tmp.extend(s.into_token_stream());
                 }
                 output = Some(tmp);
+            } else if name.to_string().as_str() == "input" && meta.len() > 2 {
+                let mut tmp = TokenStream::new();
+                for s in &meta[2..] {
+                    tmp.extend(s.into_token_stream());
+                }
+                input = Some(tmp);
             } else {
                 if meta.len() != 3 {
                     return fail!(meta[0].span(), "Invalid parameter format");
@@ -619,6 +630,7 @@ fn parse_metadata(metadata: TokenStream) -> SignatureResult<Metadata> {
         long_description,
         example,
         output,
+        input,
         condition,
     })
 }
@@ -649,6 +661,13 @@ fn signature_real(metadata: TokenStream, input: TokenStream) -> SignatureResult<
         .output
         .map(|o| quote! {#o})
         .unwrap_or(quote! {crate::lang::command::OutputType::Unknown});
+    let (declare_input, with_input) = match metadata.input {
+        Some(i) => (
+            quote! {env.declare_input(#command_name, #i)?;},
+            quote! {let command = command.with_input(#i);},
+        ),
+        None => (quote! {}, quote! {}),
+    };
 
     match root {
         Item::Struct(mut s) => {
@@ -760,16 +779,19 @@ fn signature_real(metadata: TokenStream, input: TokenStream) -> SignatureResult<
                         #signature_literal,
                         #description,
                         #long_description,
-                        #output)
+                        #output)?;
+                    #declare_input
+                    Ok(())
                 }
 
                 fn declare_method(env: &mut ordered_map::OrderedMap<std::string::String, crate::lang::command::Command>, path: &Vec<&str>) -> crate::lang::errors::CrushResult <()> {
                     let mut full = path.clone();
                     full.push(#command_name);
-                    env.insert(#command_name.to_string(),
-                                crate::lang::command::CrushCommand::command(
-                                    #command_invocation, #can_block, full.iter().map(|e| e.to_string()).collect(),
-                                    #signature_literal, #description, #long_description, #output));
+                    let command = crate::lang::command::CrushCommand::command(
+                        #command_invocation, #can_block, full.iter().map(|e| e.to_string()).collect(),
+                        #signature_literal, #description, #long_description, #output);
+                    #with_input
+                    env.insert(#command_name.to_string(), command);
                     Ok(())
                 }
 
@@ -1,11 +1,12 @@
 use crate::lang::argument::ArgumentDefinition;
 use crate::lang::command::{Command, Parameter};
 use crate::lang::command_invocation::CommandInvocation;
-use crate::lang::errors::{error, to_crush_error, CrushResult};
+use crate::lang::errors::{error, to_crush_error, CrushResult, Span};
 use crate::lang::job::Job;
 use crate::lang::scope::Scope;
 use crate::lang::value::{Value, ValueDefinition, ValueType};
 use crate::util::glob::Glob;
+use num_bigint::BigInt;
 use regex::Regex;
 use std::ops::Deref;
 use std::path::PathBuf;
@@ -22,25 +23,56 @@ impl JobListNode {
 
 pub struct JobNode {
     pub commands: Vec<CommandNode>,
+    pub background: bool,
+    pub source: Option<String>,
 }
 
 impl JobNode {
+    pub fn with_source(mut self, source: &str) -> JobNode {
+        self.source = Some(source.trim().to_string());
+        self
+    }
+
+    pub fn in_background(mut self) -> JobNode {
+        self.background = true;
+        self
+    }
+
     pub fn generate(&self, env: &Scope) -> CrushResult<Job> {
-        Ok(Job::new(
+        let mut job = Job::new(
             self.commands
                 .iter()
                 .map(|c| c.generate(env))
                 .collect::<CrushResult<Vec<CommandInvocation>>>()?,
-        ))
+        );
+        if let Some(source) = &self.source {
+            job = job.with_source(source);
+        }
+        if self.background {
+            job = job.in_background();
+        }
+        Ok(job)
     }
 }
 
 pub struct CommandNode {
     pub expressions: Vec<Node>,
+    pub span: Option<Span>,
 }
 
 impl CommandNode {
+    pub fn with_span(mut self, span: Span) -> CommandNode {
+        self.span = Some(span);
+        self
+    }
+
     pub fn generate(&self, env: &Scope) -> CrushResult<CommandInvocation> {
+        self.generate_internal(env)
+            .map(|c| c.with_span(self.span.clone()))
+            .map_err(|e| e.with_span(self.span.as_ref()))
+    }
+
+    fn generate_internal(&self, env: &Scope) -> CrushResult<CommandInvocation> {
         if let Some(c) = self.expressions[0].generate_standalone(env)? {
             if self.expressions.len() == 1 {
                 Ok(c)
@@ -72,7 +104,7 @@ pub enum Node {
     Field(String),
     String(String),
     File(PathBuf),
-    Integer(i128),
+    Integer(BigInt),
     Float(f64),
     GetItem(Box<Node>, Box<Node>),
     GetAttr(Box<Node>, String),
@@ -140,7 +172,7 @@ impl Node {
                 to_crush_error(Regex::new(l.clone().as_ref()))?,
             )),
             Node::String(t) => ValueDefinition::Value(Value::string(unescape(t).as_str())),
-            Node::Integer(i) => ValueDefinition::Value(Value::Integer(*i)),
+            Node::Integer(i) => ValueDefinition::Value(Value::from(i.clone())),
             Node::Float(f) => ValueDefinition::Value(Value::Float(*f)),
             Node::GetAttr(node, label) => {
                 let parent = node.generate_argument(env)?;
@@ -431,3 +463,33 @@ impl ParameterNode {
         }
     }
 }
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum TokenType {
+    String,
+    Field,
+    Regex,
+    Label,
+    QuotedLabel,
+    Number,
+    Flag,
+    Operator,
+    Assignment,
+    Colon,
+    Separator,
+    Pipe,
+    Background,
+    BlockStart,
+    BlockEnd,
+    SubstitutionStart,
+    SubstitutionEnd,
+    Other,
+}
+
+/** A single token of the input, used for syntax highlighting. */
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct TokenNode {
+    pub token_type: TokenType,
+    pub start: usize,
+    pub end: usize,
+}
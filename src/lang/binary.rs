@@ -5,7 +5,9 @@ use std::collections::VecDeque;
 use std::fmt::{Debug, Formatter};
 use std::fs::File;
 use std::io::{Error, Read, Write};
+use std::os::unix::io::OwnedFd;
 use std::path::PathBuf;
+use std::process::ChildStdout;
 
 struct ChannelReader {
     receiver: Receiver<Box<[u8]>>,
@@ -49,9 +51,10 @@ impl std::io::Read for ChannelReader {
                     self.buff = None;
                     Ok(res)
                 } else {
-                    dst.write_all(src)?;
-                    self.buff = Some(Box::from(&src[dst.len()..]));
-                    Ok(dst.len())
+                    let len = dst.len();
+                    dst.copy_from_slice(&src[..len]);
+                    self.buff = Some(Box::from(&src[len..]));
+                    Ok(len)
                 }
             }
         }
@@ -127,6 +130,11 @@ impl dyn BinaryReader {
         }
     }
 
+    /** Read the output of a child process as it is produced. */
+    pub fn pipe(pipe: ChildStdout) -> Box<dyn BinaryReader + Send + Sync> {
+        Box::from(FileReader::new(File::from(OwnedFd::from(pipe))))
+    }
+
     pub fn vec(vec: &Vec<u8>) -> Box<dyn BinaryReader + Send + Sync> {
         Box::from(VecReader {
             vec: vec.clone(),
@@ -213,3 +221,27 @@ impl Debug for VecReader {
         f.write_str("<vec reader>")
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn channel_reader_keeps_rest_of_chunk() {
+        let (mut writer, mut reader) = binary_channel();
+        writer.write_all(b"hello world").unwrap();
+        writer.write_all(b"!").unwrap();
+        drop(writer);
+
+        let mut buff = [0u8; 4];
+        let mut res = Vec::new();
+        loop {
+            let len = reader.read(&mut buff).unwrap();
+            if len == 0 {
+                break;
+            }
+            res.extend_from_slice(&buff[..len]);
+        }
+        assert_eq!(res, b"hello world!");
+    }
+}
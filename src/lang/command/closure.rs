@@ -1,8 +1,11 @@
 use crate::lang::argument::{Argument, ArgumentDefinition, ArgumentType};
-use crate::lang::command::{BoundCommand, Command, CrushCommand, OutputType, Parameter};
+use crate::lang::command::{
+    BoundCommand, Command, CrushCommand, InputType, OutputType, Parameter,
+};
 use crate::lang::command_invocation::CommandInvocation;
 use crate::lang::dict::Dict;
 use crate::lang::errors::{argument_error, error, mandate, CrushResult};
+use crate::lang::execute;
 use crate::lang::execution_context::{CompileContext, ExecutionContext, JobContext};
 use crate::lang::help::Help;
 use crate::lang::job::Job;
@@ -41,6 +44,10 @@ impl CrushCommand for Closure {
             return Ok(());
         }
         for (idx, job_definition) in job_definitions.iter().enumerate() {
+            if job_definition.is_background() {
+                execute::background(job_definition.clone(), &env, &context.printer);
+                continue;
+            }
             let first = idx == 0;
             let last = idx == job_definitions.len() - 1;
             let input = if first {
@@ -108,6 +115,22 @@ impl CrushCommand for Closure {
     fn output(&self, _input: &OutputType) -> Option<&ValueType> {
         None
     }
+
+    fn input(&self) -> InputType {
+        InputType::Unknown
+    }
+
+    fn with_input(&self, _input: InputType) -> Command {
+        self.copy()
+    }
+
+    fn typecheck(&self) -> CrushResult<Option<ValueType>> {
+        let mut res = None;
+        for job_definition in &self.job_definitions {
+            res = job_definition.check(&self.env)?;
+        }
+        Ok(res)
+    }
 }
 
 struct ClosureSerializer<'a> {
@@ -207,6 +230,7 @@ impl<'a> ClosureSerializer<'a> {
         for c in job.commands() {
             s.commands.push(self.command(c)?);
         }
+        s.background = job.is_background();
         Ok(s)
     }
 
@@ -365,12 +389,17 @@ impl<'a> ClosureDeserializer<'a> {
     }
 
     fn job(&mut self, s: &model::Job) -> CrushResult<Job> {
-        Ok(Job::new(
+        let job = Job::new(
             s.commands
                 .iter()
                 .map(|c| self.command(c))
                 .collect::<CrushResult<Vec<_>>>()?,
-        ))
+        );
+        Ok(if s.background {
+            job.in_background()
+        } else {
+            job
+        })
     }
 
     fn command(&mut self, s: &model::CommandInvocation) -> CrushResult<CommandInvocation> {
@@ -432,12 +461,7 @@ impl<'a> ClosureDeserializer<'a> {
                     )
                 }
                 model::value_definition::ValueDefinition::Job(j) => {
-                    ValueDefinition::JobDefinition(Job::new(
-                        j.commands
-                            .iter()
-                            .map(|c| self.command(c))
-                            .collect::<CrushResult<Vec<_>>>()?,
-                    ))
+                    ValueDefinition::JobDefinition(self.job(j)?)
                 }
                 model::value_definition::ValueDefinition::Label(s) => {
                     ValueDefinition::Label(s.clone())
@@ -43,6 +43,24 @@ impl OutputType {
     }
 }
 
+/**
+  What a command expects as its input. This is used to check pipelines before they are run, so
+  that e.g. a misspelled column name is reported before any command has started.
+*/
+#[derive(Clone, Debug)]
+pub enum InputType {
+    Unknown,
+    /** A table or a stream. Unnamed field arguments name columns of the input. */
+    Table,
+    /**
+      Like Table, but the column named by the first unnamed field argument, or the only column of
+      the input if there is no such argument, must be of one of the specified types.
+    */
+    Column(Vec<ValueType>),
+    /** Binary data, unless the files to read are given as unnamed arguments. */
+    Binary,
+}
+
 pub trait CrushCommand: Help {
     fn invoke(&self, context: ExecutionContext) -> CrushResult<()>;
     fn can_block(&self, arguments: &[ArgumentDefinition], context: &mut CompileContext) -> bool;
@@ -56,6 +74,13 @@ pub trait CrushCommand: Help {
     ) -> CrushResult<usize>;
     fn bind(&self, this: Value) -> Command;
     fn output<'a>(&'a self, input: &'a OutputType) -> Option<&'a ValueType>;
+    fn input(&self) -> InputType;
+    fn with_input(&self, input: InputType) -> Command;
+    /**
+      The type of the output of this command when invoked without input, as far as it can be known
+      without invoking it. For closures, this checks the jobs of the closure, too.
+    */
+    fn typecheck(&self) -> CrushResult<Option<ValueType>>;
 }
 
 pub trait TypeMap {
@@ -105,6 +130,7 @@ struct SimpleCommand {
     short_help: &'static str,
     long_help: Option<&'static str>,
     output: OutputType,
+    input: InputType,
 }
 
 struct ConditionCommand {
@@ -142,6 +168,7 @@ impl dyn CrushCommand {
             short_help,
             long_help,
             output,
+            input: InputType::Unknown,
         })
     }
 
@@ -211,6 +238,7 @@ impl CrushCommand for SimpleCommand {
             short_help: self.short_help,
             long_help: self.long_help,
             output: self.output.clone(),
+            input: self.input.clone(),
         })
     }
 
@@ -242,6 +270,27 @@ impl CrushCommand for SimpleCommand {
     fn output<'a>(&'a self, input: &'a OutputType) -> Option<&'a ValueType> {
         self.output.calculate(input)
     }
+
+    fn input(&self) -> InputType {
+        self.input.clone()
+    }
+
+    fn with_input(&self, input: InputType) -> Command {
+        Box::from(SimpleCommand {
+            call: self.call,
+            can_block: self.can_block,
+            full_name: self.full_name.clone(),
+            signature: self.signature,
+            short_help: self.short_help,
+            long_help: self.long_help,
+            output: self.output.clone(),
+            input,
+        })
+    }
+
+    fn typecheck(&self) -> CrushResult<Option<ValueType>> {
+        Ok(self.output.calculate(&OutputType::Unknown).cloned())
+    }
 }
 
 impl Help for SimpleCommand {
@@ -333,6 +382,18 @@ impl CrushCommand for ConditionCommand {
     fn output(&self, _input: &OutputType) -> Option<&ValueType> {
         None
     }
+
+    fn input(&self) -> InputType {
+        InputType::Unknown
+    }
+
+    fn with_input(&self, _input: InputType) -> Command {
+        self.copy()
+    }
+
+    fn typecheck(&self) -> CrushResult<Option<ValueType>> {
+        Ok(None)
+    }
 }
 
 impl Help for ConditionCommand {
@@ -439,6 +500,21 @@ impl CrushCommand for BoundCommand {
     fn output<'a>(&'a self, input: &'a OutputType) -> Option<&'a ValueType> {
         self.command.output(input)
     }
+
+    fn input(&self) -> InputType {
+        self.command.input()
+    }
+
+    fn with_input(&self, input: InputType) -> Command {
+        Box::from(BoundCommand {
+            command: self.command.with_input(input),
+            this: self.this.clone(),
+        })
+    }
+
+    fn typecheck(&self) -> CrushResult<Option<ValueType>> {
+        self.command.typecheck()
+    }
 }
 
 impl Help for BoundCommand {
@@ -1,11 +1,13 @@
-use crate::lang::errors::{error, CrushResult, Kind};
+use crate::lang::errors::{error, CrushResult, Kind, Span};
 use crate::lang::execution_context::{CompileContext, JobContext};
 use crate::lang::scope::Scope;
+use crate::lang::typecheck;
 use crate::lang::{argument::ArgumentDefinition, argument::ArgumentVecCompiler, value::Value};
 use crate::lang::{
     command::Command, execution_context::ExecutionContext, job::JobJoinHandle,
-    value::ValueDefinition,
+    value::ValueDefinition, value::ValueType,
 };
+use crate::util::executable::{self, is_executable};
 use crate::util::thread::{build, handle};
 use std::ops::Deref;
 use std::path::PathBuf;
@@ -14,22 +16,34 @@ use std::path::PathBuf;
 pub struct CommandInvocation {
     command: ValueDefinition,
     arguments: Vec<ArgumentDefinition>,
+    span: Option<Span>,
 }
 
-fn resolve_external_command(name: &str, env: &Scope) -> CrushResult<Option<PathBuf>> {
+/** The name and source location of an invocation, used to annotate the errors it causes. */
+#[derive(Clone)]
+struct Origin {
+    command: String,
+    span: Option<Span>,
+}
+
+impl Origin {
+    fn annotate<T>(&self, result: CrushResult<T>) -> CrushResult<T> {
+        result.map_err(|e| e.with_command(&self.command, self.span.as_ref()))
+    }
+}
+
+/** Find the external command with the specified name in the directories listed in `cmd_path`. */
+pub fn resolve_external_command(name: &str, env: &Scope) -> CrushResult<Option<PathBuf>> {
     if let Some(Value::List(path)) = env.get("cmd_path")? {
-        let path_vec = path.dump();
-        for val in path_vec {
-            match val {
-                Value::File(el) => {
-                    let full = el.join(name);
-                    if full.exists() {
-                        return Ok(Some(full));
-                    }
-                }
-                _ => {}
-            }
-        }
+        let dirs = path
+            .dump()
+            .drain(..)
+            .filter_map(|val| match val {
+                Value::File(dir) => Some(dir),
+                _ => None,
+            })
+            .collect::<Vec<_>>();
+        return Ok(executable::find(name, &dirs));
     }
     Ok(None)
 }
@@ -45,7 +59,23 @@ fn arg_can_block(local_arguments: &Vec<ArgumentDefinition>, context: &mut Compil
 
 impl CommandInvocation {
     pub fn new(command: ValueDefinition, arguments: Vec<ArgumentDefinition>) -> CommandInvocation {
-        CommandInvocation { command, arguments }
+        CommandInvocation {
+            command,
+            arguments,
+            span: None,
+        }
+    }
+
+    pub fn with_span(mut self, span: Option<Span>) -> CommandInvocation {
+        self.span = span;
+        self
+    }
+
+    fn origin(&self) -> Origin {
+        Origin {
+            command: self.command.to_string(),
+            span: self.span.clone(),
+        }
     }
 
     pub fn as_string(&self) -> Option<String> {
@@ -67,6 +97,16 @@ impl CommandInvocation {
         &self.command
     }
 
+    /** Check this invocation before it is run, see typecheck::invocation. */
+    pub fn check(&self, input: Option<&ValueType>, env: &Scope) -> CrushResult<Option<ValueType>> {
+        self.origin().annotate(typecheck::invocation(
+            &self.command,
+            &self.arguments,
+            input,
+            env,
+        ))
+    }
+
     /*
         pub fn spawn_stream(
             &self,
@@ -120,33 +160,46 @@ impl CommandInvocation {
     }
 
     pub fn invoke(&self, context: JobContext) -> CrushResult<JobJoinHandle> {
+        let origin = self.origin();
         match self
             .command
             .compile_internal(&mut context.compile_context(), false)
         {
-            Ok((this, value)) => invoke_value(this, value, self.arguments.clone(), context),
+            Ok((this, value)) => origin.annotate(invoke_value(
+                this,
+                value,
+                self.arguments.clone(),
+                context,
+                origin.clone(),
+            )),
             Err(err) => {
                 if err.kind == Kind::BlockError {
                     let cmd = self.command.clone();
                     let arguments = self.arguments.clone();
                     Ok(handle(build(self.command.to_string().as_str()).spawn(
                         move || match cmd.clone().compile_unbound(&mut context.compile_context()) {
-                            Ok((this, value)) => context.printer.handle_error(invoke_value(
-                                this,
-                                value,
-                                arguments,
-                                context.clone(),
-                            )),
+                            Ok((this, value)) => {
+                                context.printer.handle_error(origin.annotate(invoke_value(
+                                    this,
+                                    value,
+                                    arguments,
+                                    context.clone(),
+                                    origin.clone(),
+                                )))
+                            }
 
-                            _ => context.printer.handle_error(try_external_command(
-                                cmd,
-                                arguments,
-                                context.clone(),
+                            _ => context.printer.handle_error(origin.annotate(
+                                try_external_command(cmd, arguments, context.clone(), &origin),
                             )),
                         },
                     )))
                 } else {
-                    try_external_command(self.command.clone(), self.arguments.clone(), context)
+                    origin.annotate(try_external_command(
+                        self.command.clone(),
+                        self.arguments.clone(),
+                        context,
+                        &origin,
+                    ))
                 }
             }
         }
@@ -158,11 +211,14 @@ fn invoke_value(
     value: Value,
     local_arguments: Vec<ArgumentDefinition>,
     context: JobContext,
+    origin: Origin,
 ) -> CrushResult<JobJoinHandle> {
     match value {
-        Value::Command(command) => invoke_command(command, this, local_arguments, context),
+        Value::Command(command) => invoke_command(command, this, local_arguments, context, origin),
         Value::File(f) => {
-            if local_arguments.len() == 0 {
+            if is_executable(&f) {
+                external_command(f, None, local_arguments, context, origin)
+            } else if local_arguments.len() == 0 {
                 let meta = f.metadata();
                 if meta.is_ok() && meta.unwrap().is_dir() {
                     invoke_command(
@@ -174,6 +230,7 @@ fn invoke_value(
                             Value::File(f),
                         ))],
                         context,
+                        origin,
                     )
                 } else {
                     invoke_command(
@@ -183,6 +240,7 @@ fn invoke_value(
                             Value::File(f),
                         ))],
                         context,
+                        origin,
                     )
                 }
             } else {
@@ -203,12 +261,14 @@ fn invoke_value(
                     Value::Type(t),
                 ))],
                 context,
+                origin,
             ),
             Some(call) => invoke_command(
                 call.as_ref().copy(),
                 Some(Value::Type(t)),
                 local_arguments,
                 context,
+                origin,
             ),
         },
         _ => {
@@ -218,6 +278,7 @@ fn invoke_value(
                     None,
                     vec![ArgumentDefinition::unnamed(ValueDefinition::Value(value))],
                     context,
+                    origin,
                 )
             } else {
                 error(format!("Not a command {}", value.to_string()).as_str())
@@ -231,22 +292,25 @@ fn invoke_command(
     this: Option<Value>,
     local_arguments: Vec<ArgumentDefinition>,
     context: JobContext,
+    origin: Origin,
 ) -> CrushResult<JobJoinHandle> {
     if !action.can_block(&local_arguments, &mut context.compile_context())
         && !arg_can_block(&local_arguments, &mut context.compile_context())
     {
         let new_context =
             CommandInvocation::execution_context(local_arguments, this, context.clone())?;
-        context.printer.handle_error(action.invoke(new_context));
+        context
+            .printer
+            .handle_error(origin.annotate(action.invoke(new_context)));
         Ok(JobJoinHandle::Many(vec![]))
     } else {
         Ok(handle(build(action.name()).spawn(move || {
             let res = CommandInvocation::execution_context(local_arguments, this, context.clone());
             if let Ok(ctx) = res {
                 let p = ctx.printer.clone();
-                p.handle_error(action.invoke(ctx));
+                p.handle_error(origin.annotate(action.invoke(ctx)));
             } else {
-                context.printer.handle_error(res);
+                context.printer.handle_error(origin.annotate(res));
             }
         })))
     }
@@ -254,8 +318,9 @@ fn invoke_command(
 
 fn try_external_command(
     def: ValueDefinition,
-    mut arguments: Vec<ArgumentDefinition>,
+    arguments: Vec<ArgumentDefinition>,
     context: JobContext,
+    origin: &Origin,
 ) -> CrushResult<JobJoinHandle> {
     let (cmd, sub) = match def {
         ValueDefinition::Label(str) => (str, None),
@@ -268,30 +333,39 @@ fn try_external_command(
 
     match resolve_external_command(&cmd, &context.env)? {
         None => error(format!("Unknown command name {}", cmd).as_str()),
-        Some(path) => {
-            arguments.insert(
-                0,
-                ArgumentDefinition::unnamed(ValueDefinition::Value(Value::File(path))),
-            );
-            if let Some(subcmd) = sub {
-                arguments.insert(
-                    1,
-                    ArgumentDefinition::unnamed(ValueDefinition::Value(Value::string(
-                        subcmd.as_ref(),
-                    ))),
-                );
-            }
-            let call = CommandInvocation {
-                command: ValueDefinition::Value(Value::Command(
-                    context
-                        .env
-                        .global_static_cmd(vec!["global", "control", "cmd"])?,
-                )),
-                arguments,
-            };
-            call.invoke(context)
-        }
+        Some(path) => external_command(path, sub, arguments, context, origin.clone()),
+    }
+}
+
+/** Run an executable through the cmd command. A subcommand, if any, becomes the first argument. */
+fn external_command(
+    path: PathBuf,
+    sub: Option<String>,
+    mut arguments: Vec<ArgumentDefinition>,
+    context: JobContext,
+    origin: Origin,
+) -> CrushResult<JobJoinHandle> {
+    arguments.insert(
+        0,
+        ArgumentDefinition::unnamed(ValueDefinition::Value(Value::File(path))),
+    );
+    if let Some(subcmd) = sub {
+        arguments.insert(
+            1,
+            ArgumentDefinition::unnamed(ValueDefinition::Value(Value::string(subcmd.as_ref()))),
+        );
     }
+    invoke_value(
+        None,
+        Value::Command(
+            context
+                .env
+                .global_static_cmd(vec!["global", "control", "cmd"])?,
+        ),
+        arguments,
+        context,
+        origin,
+    )
 }
 
 impl ToString for CommandInvocation {
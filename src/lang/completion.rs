@@ -0,0 +1,302 @@
+use crate::lang::command::OutputType;
+use crate::lang::highlight::highlight;
+use crate::lang::scope::Scope;
+use crate::lang::value::{Value, ValueType};
+use ordered_map::OrderedMap;
+use rustyline::completion::{Completer, Pair};
+use rustyline::highlight::Highlighter;
+use rustyline::hint::Hinter;
+use rustyline::{Context, Helper};
+use std::borrow::Cow::{self, Owned};
+use std::path::PathBuf;
+
+/**
+  Completion and syntax highlighting for the interactive loop.
+
+  The word under the cursor is looked at in isolation, and depending on what it looks like it is
+  completed as a `^field` of the input of the current command, a `:member` of a namespace or
+  value, a named argument of the current command, a file or a variable in the current scope.
+*/
+pub struct CrushHelper {
+    scope: Scope,
+}
+
+impl CrushHelper {
+    pub fn new(scope: Scope) -> CrushHelper {
+        CrushHelper { scope }
+    }
+}
+
+impl Helper for CrushHelper {}
+
+impl Hinter for CrushHelper {}
+
+impl Highlighter for CrushHelper {
+    fn highlight<'l>(&self, line: &'l str, _pos: usize) -> Cow<'l, str> {
+        Owned(highlight(line, &self.scope))
+    }
+
+    fn highlight_char(&self, _line: &str, _pos: usize) -> bool {
+        true
+    }
+}
+
+impl Completer for CrushHelper {
+    type Candidate = Pair;
+
+    fn complete(
+        &self,
+        line: &str,
+        pos: usize,
+        _ctx: &Context<'_>,
+    ) -> rustyline::Result<(usize, Vec<Pair>)> {
+        Ok(complete(line, pos, &self.scope))
+    }
+}
+
+fn is_separator(c: char) -> bool {
+    c.is_whitespace() || "|;{}()&=".contains(c)
+}
+
+/** Find the byte offset where the word that ends at `pos` starts. Quoted strings are skipped whole. */
+fn word_start(line: &str, pos: usize) -> usize {
+    let mut in_quote = false;
+    let mut start = 0;
+    for (idx, c) in line[..pos].char_indices() {
+        if c == '"' {
+            in_quote = !in_quote;
+        } else if !in_quote && is_separator(c) {
+            start = idx + c.len_utf8();
+        }
+    }
+    start
+}
+
+/**
+  The commands of the pipeline that the cursor is in, up to and including the partial command
+  under the cursor. Closures start a new pipeline, which replaces the surrounding one until the
+  closure is closed again.
+*/
+fn current_pipeline(line: &str) -> Vec<Vec<String>> {
+    let mut stack = vec![vec![String::new()]];
+    let mut in_quote = false;
+    for c in line.chars() {
+        if c == '"' {
+            in_quote = !in_quote;
+        }
+        if in_quote {
+            stack.last_mut().unwrap().last_mut().unwrap().push(c);
+            continue;
+        }
+        match c {
+            '{' | '(' => stack.push(vec![String::new()]),
+            '}' | ')' => {
+                if stack.len() > 1 {
+                    stack.pop();
+                }
+            }
+            '|' => stack.last_mut().unwrap().push(String::new()),
+            ';' | '\n' | '&' => *stack.last_mut().unwrap() = vec![String::new()],
+            c => stack.last_mut().unwrap().last_mut().unwrap().push(c),
+        }
+    }
+    stack
+}
+
+fn lookup(path: &str, scope: &Scope) -> Option<Value> {
+    if path.starts_with('"') && path.ends_with('"') && path.len() >= 2 {
+        return Some(Value::string(&path[1..path.len() - 1]));
+    }
+    let mut parts = path.split(':');
+    let mut value = scope.get(parts.next()?).ok()??;
+    for part in parts {
+        value = value.field(part).ok()??;
+    }
+    Some(value)
+}
+
+fn member_names(value: &Value) -> Vec<String> {
+    match value {
+        Value::Scope(subscope) => {
+            let mut map = OrderedMap::new();
+            let _ = subscope.dump(&mut map);
+            let mut res = map.keys().cloned().collect::<Vec<_>>();
+            res.sort();
+            res
+        }
+        _ => value.fields(),
+    }
+}
+
+fn scope_names(scope: &Scope) -> Vec<String> {
+    let mut map: OrderedMap<String, ValueType> = OrderedMap::new();
+    let _ = scope.dump(&mut map);
+    let mut res = map.keys().cloned().collect::<Vec<_>>();
+    res.sort();
+    res
+}
+
+fn command_name(command: &str) -> Option<&str> {
+    command.split_whitespace().next()
+}
+
+/** The names of all the named arguments mentioned in the signature of a command. */
+fn argument_names(command: &str, scope: &Scope) -> Vec<String> {
+    match command_name(command).and_then(|name| lookup(name, scope)) {
+        Some(Value::Command(cmd)) => cmd
+            .help()
+            .signature()
+            .split_whitespace()
+            .skip(1)
+            .map(|param| param.trim_start_matches('['))
+            .filter(|param| !param.starts_with('@') && !param.starts_with('<'))
+            .filter_map(|param| param.split(['=', ':']).next())
+            .filter(|name| !name.is_empty())
+            .map(|name| name.to_string())
+            .collect(),
+        _ => Vec::new(),
+    }
+}
+
+/** The columns of the input of the last command in the pipeline, as far as they can be known. */
+fn input_columns(pipeline: &[String], scope: &Scope) -> Vec<String> {
+    let mut output = OutputType::Unknown;
+    for command in &pipeline[..pipeline.len() - 1] {
+        output = match command_name(command).and_then(|name| lookup(name, scope)) {
+            Some(Value::Command(cmd)) => match cmd.output(&output) {
+                Some(t) => OutputType::Known(t.clone()),
+                None => OutputType::Unknown,
+            },
+            _ => OutputType::Unknown,
+        };
+    }
+    match output {
+        OutputType::Known(ValueType::TableStream(columns))
+        | OutputType::Known(ValueType::Table(columns)) => {
+            columns.iter().map(|c| c.name.to_string()).collect()
+        }
+        _ => Vec::new(),
+    }
+}
+
+fn files(partial: &str) -> Vec<(String, bool)> {
+    let (dir, prefix) = match partial.rfind('/') {
+        Some(idx) => (&partial[..idx + 1], &partial[idx + 1..]),
+        None => ("", partial),
+    };
+    let entries = match std::fs::read_dir(if dir.is_empty() {
+        PathBuf::from(".")
+    } else {
+        PathBuf::from(dir)
+    }) {
+        Ok(entries) => entries,
+        Err(_) => return Vec::new(),
+    };
+    let mut res = entries
+        .filter_map(|e| e.ok())
+        .filter_map(|e| {
+            let name = e.file_name().to_str()?.to_string();
+            if !name.starts_with(prefix) || (prefix.is_empty() && name.starts_with('.')) {
+                return None;
+            }
+            Some((
+                format!("{}{}", dir, name),
+                e.file_type().map(|t| t.is_dir()).unwrap_or(false),
+            ))
+        })
+        .collect::<Vec<_>>();
+    res.sort();
+    res
+}
+
+fn candidates(names: Vec<String>, prefix: &str, suffix: &str) -> Vec<Pair> {
+    names
+        .into_iter()
+        .filter(|name| name.starts_with(prefix))
+        .map(|name| Pair {
+            display: name.clone(),
+            replacement: format!("{}{}", name, suffix),
+        })
+        .collect()
+}
+
+pub fn complete(line: &str, pos: usize, scope: &Scope) -> (usize, Vec<Pair>) {
+    let start = word_start(line, pos);
+    let word = &line[start..pos];
+    let stack = current_pipeline(&line[..start]);
+
+    if let Some(field) = word.strip_prefix('^') {
+        for pipeline in stack.iter().rev() {
+            let columns = input_columns(pipeline, scope);
+            if !columns.is_empty() {
+                return (start + 1, candidates(columns, field, ""));
+            }
+        }
+        return (start + 1, Vec::new());
+    }
+
+    if let Some(idx) = word.rfind(':') {
+        let members = lookup(&word[..idx], scope)
+            .map(|v| member_names(&v))
+            .unwrap_or_default();
+        return (start + idx + 1, candidates(members, &word[idx + 1..], ""));
+    }
+
+    let pipeline = stack.last().unwrap();
+    let command = pipeline.last().unwrap();
+    let mut res = Vec::new();
+    if command.trim().is_empty() {
+        res.append(&mut candidates(scope_names(scope), word, ""));
+    } else {
+        if !word.contains('/') {
+            res.append(&mut candidates(argument_names(command, scope), word, "="));
+            res.append(&mut candidates(scope_names(scope), word, ""));
+        }
+        for (name, is_dir) in files(word) {
+            res.push(Pair {
+                display: name.clone(),
+                replacement: if is_dir { format!("{}/", name) } else { name },
+            });
+        }
+    }
+    (start, res)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn replacements(line: &str, scope: &Scope) -> (usize, Vec<String>) {
+        let (start, res) = complete(line, line.len(), scope);
+        (start, res.into_iter().map(|p| p.replacement).collect())
+    }
+
+    #[test]
+    fn word_boundaries() {
+        assert_eq!(word_start("ls | sort ^na", 13), 10);
+        assert_eq!(word_start("echo \"a b\":len", 14), 5);
+        assert_eq!(word_start("where {foo", 10), 7);
+        assert_eq!(word_start("find recursive=tr", 17), 15);
+    }
+
+    #[test]
+    fn pipelines() {
+        let stack = current_pipeline("a | b; c | d {e | ");
+        assert_eq!(stack.len(), 2);
+        assert_eq!(stack[0], vec![" c ", " d "]);
+        assert_eq!(stack[1], vec!["e ", " "]);
+    }
+
+    #[test]
+    fn variables_and_members() {
+        let scope = Scope::create_root();
+        scope.declare("some_value", Value::Integer(1)).unwrap();
+        scope.declare("something", Value::Integer(1)).unwrap();
+        let (start, res) = replacements("echo 1; som", &scope);
+        assert_eq!(start, 8);
+        assert_eq!(res, vec!["some_value", "something"]);
+        let (start, res) = replacements("\"abc\":up", &scope);
+        assert_eq!(start, 6);
+        assert_eq!(res, vec!["upper"]);
+    }
+}
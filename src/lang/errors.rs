@@ -1,9 +1,12 @@
 use crate::lang::errors::Kind::*;
+use crate::lang::value::Value;
+use lalrpop_util::ParseError;
 use std::error::Error;
+use std::fmt::Display;
 
-#[derive(Debug, PartialEq)]
+#[derive(Clone, Copy, Debug, PartialEq)]
 pub enum Kind {
-    //    ParseError,
+    ParseError,
     InvalidArgument,
     InvalidData,
     GenericError,
@@ -11,49 +14,214 @@ pub enum Kind {
     SendError,
 }
 
-#[derive(Debug)]
+impl ToString for Kind {
+    fn to_string(&self) -> String {
+        match self {
+            ParseError => "parse_error",
+            InvalidArgument => "invalid_argument",
+            InvalidData => "invalid_data",
+            GenericError => "generic_error",
+            BlockError => "block_error",
+            SendError => "send_error",
+        }
+        .to_string()
+    }
+}
+
+/**
+  A location in the source code. Only the line the location starts on is kept, since that is all
+  that is needed to point at the offending code in an error message.
+*/
+#[derive(Clone, Debug, PartialEq)]
+pub struct Span {
+    pub line: usize,
+    pub column: usize,
+    pub end_line: usize,
+    pub end_column: usize,
+    pub text: String,
+}
+
+/** The largest offset not after the specified one that is not inside of a character. */
+fn char_boundary(input: &str, mut offset: usize) -> usize {
+    while !input.is_char_boundary(offset) {
+        offset -= 1;
+    }
+    offset
+}
+
+fn line_and_column(input: &str, offset: usize) -> (usize, usize) {
+    let line_start = input[..offset].rfind('\n').map(|i| i + 1).unwrap_or(0);
+    (
+        input[..offset].matches('\n').count() + 1,
+        input[line_start..offset].chars().count() + 1,
+    )
+}
+
+impl Span {
+    /** The span between two byte offsets of the input. Lines and columns start at 1. */
+    pub fn new(input: &str, start: usize, end: usize) -> Span {
+        let start = char_boundary(input, start.min(input.len()));
+        let end = char_boundary(input, end.max(start).min(input.len()));
+        let line_start = input[..start].rfind('\n').map(|i| i + 1).unwrap_or(0);
+        let line_end = input[start..]
+            .find('\n')
+            .map(|i| start + i)
+            .unwrap_or_else(|| input.len());
+        let (line, column) = line_and_column(input, start);
+        let (end_line, end_column) = line_and_column(input, end);
+        Span {
+            line,
+            column,
+            end_line,
+            end_column,
+            text: input[line_start..line_end].to_string(),
+        }
+    }
+}
+
+#[derive(Clone, Debug)]
 pub struct CrushError {
     pub kind: Kind,
     pub message: String,
+    pub command: Option<String>,
+    pub span: Option<Span>,
+    pub cause: Option<Box<CrushError>>,
 }
 
+impl CrushError {
+    pub fn new(kind: Kind, message: impl Into<String>) -> CrushError {
+        CrushError {
+            kind,
+            message: message.into(),
+            command: None,
+            span: None,
+            cause: None,
+        }
+    }
+
+    /**
+      Record the command that failed, and where in the source it was called. Errors keep the
+      innermost command, so an error in a closure points at the command inside of the closure
+      and not at the command that called the closure.
+    */
+    pub fn with_command(mut self, command: &str, span: Option<&Span>) -> CrushError {
+        if self.command.is_none() {
+            self.command = Some(command.to_string());
+            if self.span.is_none() {
+                self.span = span.cloned();
+            }
+        }
+        self
+    }
+
+    pub fn with_span(mut self, span: Option<&Span>) -> CrushError {
+        if self.span.is_none() {
+            self.span = span.cloned();
+        }
+        self
+    }
+
+    pub fn with_cause(mut self, cause: CrushError) -> CrushError {
+        self.cause = Some(Box::from(cause));
+        self
+    }
+
+    /** The fields of an error, as seen from Crush code. Missing information is empty. */
+    pub fn field(&self, name: &str) -> Option<Value> {
+        let optional = |v: Option<Value>| v.unwrap_or(Value::Empty());
+        match name {
+            "kind" => Some(Value::string(&self.kind.to_string())),
+            "message" => Some(Value::string(&self.message)),
+            "command" => Some(optional(self.command.as_deref().map(Value::string))),
+            "line" => Some(optional(
+                self.span.as_ref().map(|s| Value::Integer(s.line as i128)),
+            )),
+            "column" => Some(optional(
+                self.span.as_ref().map(|s| Value::Integer(s.column as i128)),
+            )),
+            "cause" => Some(optional(
+                self.cause
+                    .as_ref()
+                    .map(|c| Value::Error(c.as_ref().clone())),
+            )),
+            _ => None,
+        }
+    }
+}
+
+pub const ERROR_FIELDS: [&str; 6] = ["kind", "message", "command", "line", "column", "cause"];
+
 pub type CrushResult<T> = Result<T, CrushError>;
 
 pub fn block_error<T>() -> Result<T, CrushError> {
-    Err(CrushError {
-        message: String::from(
-            "Internal error: Tried to call blocking code in a thread that may not block",
-        ),
-        kind: BlockError,
-    })
+    Err(CrushError::new(
+        BlockError,
+        "Internal error: Tried to call blocking code in a thread that may not block",
+    ))
 }
 
 pub fn send_error<T>() -> Result<T, CrushError> {
-    Err(CrushError {
-        message: String::from("Tried to send data to a command that is no longer listening. This is almost normal behaviour and can be safely ignored."),
-        kind: SendError,
-    })
+    Err(CrushError::new(SendError, "Tried to send data to a command that is no longer listening. This is almost normal behaviour and can be safely ignored."))
 }
 
 pub fn argument_error<T>(message: &str) -> Result<T, CrushError> {
-    Err(CrushError {
-        message: String::from(message),
-        kind: InvalidArgument,
-    })
+    Err(CrushError::new(InvalidArgument, message))
 }
 
 pub fn data_error<T>(message: &str) -> Result<T, CrushError> {
-    Err(CrushError {
-        message: String::from(message),
-        kind: InvalidData,
-    })
+    Err(CrushError::new(InvalidData, message))
 }
 
 pub fn error<T>(message: impl Into<String>) -> Result<T, CrushError> {
-    Err(CrushError {
-        message: message.into(),
-        kind: GenericError,
-    })
+    Err(CrushError::new(GenericError, message))
+}
+
+fn expected_tokens(expected: &[String]) -> String {
+    match expected.len() {
+        0 => "".to_string(),
+        1 => format!(", expected {}", expected[0]),
+        _ => format!(", expected one of {}", expected.join(", ")),
+    }
+}
+
+/** Convert an error from the parser into a parse error pointing at the offending token. */
+pub fn parse_error<T, Tok: Display, E: Display>(
+    input: &str,
+    err: ParseError<usize, Tok, E>,
+) -> Result<T, CrushError> {
+    let (message, start, end) = match err {
+        ParseError::InvalidToken { location } => (
+            "Invalid token".to_string(),
+            location,
+            location
+                + input
+                    .get(location..)
+                    .and_then(|s| s.chars().next())
+                    .map_or(0, char::len_utf8),
+        ),
+        ParseError::UnrecognizedEOF { location, expected } => (
+            format!("Unexpected end of input{}", expected_tokens(&expected)),
+            location,
+            location,
+        ),
+        ParseError::UnrecognizedToken {
+            token: (start, token, end),
+            expected,
+        } => (
+            format!(
+                "Unexpected token {:?}{}",
+                token.to_string(),
+                expected_tokens(&expected)
+            ),
+            start,
+            end,
+        ),
+        ParseError::ExtraToken {
+            token: (start, token, end),
+        } => (format!("Extra token {:?}", token.to_string()), start, end),
+        ParseError::User { error } => (error.to_string(), 0, 0),
+    };
+    Err(CrushError::new(Kind::ParseError, message).with_span(Some(&Span::new(input, start, end))))
 }
 
 pub fn to_crush_error<T, E: Error>(result: Result<T, E>) -> Result<T, CrushError> {
@@ -69,3 +237,43 @@ pub fn mandate<T>(result: Option<T>, msg: &str) -> Result<T, CrushError> {
         None => error(msg),
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn span_lines_and_columns() {
+        let input = "echo 1\nseq 3 | nosuch\necho 2";
+        let start = input.find("nosuch").unwrap();
+        let span = Span::new(input, start, start + 6);
+        assert_eq!((span.line, span.column), (2, 9));
+        assert_eq!((span.end_line, span.end_column), (2, 15));
+        assert_eq!(span.text, "seq 3 | nosuch");
+    }
+
+    #[test]
+    fn invalid_non_ascii_token() {
+        let input = "echo é|";
+        let start = input.find('é').unwrap();
+        let err = parse_error::<(), &str, &str>(
+            input,
+            ParseError::InvalidToken { location: start },
+        )
+        .unwrap_err();
+        let span = err.span.unwrap();
+        assert_eq!((span.column, span.end_column), (6, 7));
+
+        let span = Span::new(input, start + 1, start + 2);
+        assert_eq!((span.column, span.end_column), (6, 7));
+    }
+
+    #[test]
+    fn innermost_command_is_kept() {
+        let err = CrushError::new(GenericError, "oops")
+            .with_command("inner", None)
+            .with_command("outer", Some(&Span::new("outer", 0, 5)));
+        assert_eq!(err.command, Some("inner".to_string()));
+        assert_eq!(err.span, None);
+    }
+}
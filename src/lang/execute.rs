@@ -1,6 +1,8 @@
 use crate::lang::errors::{argument_error, to_crush_error, CrushError, CrushResult};
 use crate::lang::execution_context::{ExecutionContext, JobContext};
+use crate::lang::job::{jobs, Job};
 use crate::lang::parser::parse;
+use crate::lang::pretty_printer::create_pretty_printer;
 use crate::lang::printer::Printer;
 use crate::lang::scope::Scope;
 use crate::lang::serialization::{deserialize, serialize};
@@ -60,6 +62,14 @@ pub fn string(global_env: Scope, s: &str, printer: &Printer, output: &ValueSende
     match parse(s, &global_env) {
         Ok(jobs) => {
             for job_definition in jobs {
+                if let Err(e) = job_definition.check(&global_env) {
+                    printer.crush_error(e);
+                    continue;
+                }
+                if job_definition.is_background() {
+                    background(job_definition, &global_env, printer);
+                    continue;
+                }
                 match job_definition.invoke(JobContext::new(
                     empty_channel(),
                     output.clone(),
@@ -78,3 +88,23 @@ pub fn string(global_env: Scope, s: &str, printer: &Printer, output: &ValueSende
         }
     }
 }
+
+/**
+Start a job without waiting for it. The job gets a pretty printer of its own, so that a long
+running table stream in the background does not hold up the output of later foreground jobs.
+*/
+pub fn background(job_definition: Job, env: &Scope, printer: &Printer) {
+    let (job_printer, failed) = printer.tracked();
+    match job_definition.invoke(JobContext::new(
+        empty_channel(),
+        create_pretty_printer(job_printer.clone()),
+        env.clone(),
+        job_printer,
+    )) {
+        Ok(handle) => {
+            let id = jobs().add(&job_definition.source(), handle, failed);
+            printer.line(format!("[{}] {}", id, job_definition.source()).as_str());
+        }
+        Err(e) => printer.crush_error(e),
+    }
+}
@@ -13,6 +13,7 @@ use crate::lang::value::{Value, ValueType};
 use crate::util::glob::Glob;
 use crate::util::replace::Replace;
 use chrono::{DateTime, Duration, Local};
+use num_bigint::BigInt;
 use regex::Regex;
 use std::path::PathBuf;
 
@@ -303,6 +304,7 @@ pub trait This {
     fn re(self) -> CrushResult<(String, Regex)>;
     fn glob(self) -> CrushResult<Glob>;
     fn integer(self) -> CrushResult<i128>;
+    fn big_integer(self) -> CrushResult<BigInt>;
     fn float(self) -> CrushResult<f64>;
     fn r#type(self) -> CrushResult<ValueType>;
     fn duration(self) -> CrushResult<Duration>;
@@ -352,6 +354,13 @@ impl This for Option<Value> {
     this_method!(scope, Scope, Scope, "scope");
     this_method!(table_stream, InputStream, TableStream, "table_stream");
 
+    fn big_integer(mut self) -> CrushResult<BigInt> {
+        match self.take().as_ref().and_then(Value::big_integer) {
+            Some(i) => Ok(i),
+            None => argument_error("Expected this to be an integer"),
+        }
+    }
+
     fn re(mut self) -> CrushResult<(String, Regex)> {
         match self.take() {
             Some(Value::Regex(s, b)) => Ok((s, b)),
@@ -0,0 +1,125 @@
+use crate::lang::ast::{TokenNode, TokenType};
+use crate::lang::parser::tokenize;
+use crate::lang::scope::Scope;
+use termion::color;
+
+/**
+  Returns true if the label is used as a command name that can't be found in the scope.
+  Labels containing a slash are paths and labels that are assigned to are variable names,
+  so neither of those are ever reported as unknown.
+*/
+fn is_unknown_command(label: &str, next: Option<&TokenNode>, scope: &Scope) -> bool {
+    if label.contains('/') {
+        return false;
+    }
+    if let Some(TokenType::Assignment) = next.map(|t| t.token_type) {
+        return false;
+    }
+    match scope.get(label) {
+        Ok(value) => value.is_none(),
+        Err(_) => false,
+    }
+}
+
+fn token_color(token_type: TokenType) -> Option<String> {
+    match token_type {
+        TokenType::String | TokenType::QuotedLabel => Some(color::Fg(color::Yellow).to_string()),
+        TokenType::Field => Some(color::Fg(color::Cyan).to_string()),
+        TokenType::Regex => Some(color::Fg(color::Magenta).to_string()),
+        TokenType::Operator | TokenType::Assignment | TokenType::Pipe | TokenType::Background => {
+            Some(color::Fg(color::Blue).to_string())
+        }
+        _ => None,
+    }
+}
+
+/** Add ANSI color codes to a line of input, based on the tokens the parser would see. */
+pub fn highlight(line: &str, scope: &Scope) -> String {
+    let tokens = tokenize(line);
+    let mut res = String::with_capacity(line.len() * 2);
+    let mut last = 0;
+    let mut expect_command = true;
+    let mut block_start = false;
+    let mut in_signature = false;
+
+    for (idx, token) in tokens.iter().enumerate() {
+        res.push_str(&line[last..token.start]);
+        let text = &line[token.start..token.end];
+        let color = match token.token_type {
+            TokenType::Label
+                if expect_command
+                    && !in_signature
+                    && is_unknown_command(text, tokens.get(idx + 1), scope) =>
+            {
+                Some(color::Fg(color::Red).to_string())
+            }
+            t => token_color(t),
+        };
+        match color {
+            Some(color) => {
+                res.push_str(&color);
+                res.push_str(text);
+                res.push_str(&color::Fg(color::Reset).to_string());
+            }
+            None => res.push_str(text),
+        }
+        last = token.end;
+
+        match token.token_type {
+            TokenType::Pipe if in_signature => {
+                in_signature = false;
+                expect_command = true;
+            }
+            TokenType::Pipe if block_start => in_signature = true,
+            TokenType::Separator if block_start => {}
+            TokenType::BlockStart => {
+                block_start = true;
+                expect_command = true;
+                continue;
+            }
+            TokenType::Pipe
+            | TokenType::Separator
+            | TokenType::Background
+            | TokenType::SubstitutionStart => expect_command = true,
+            _ => {
+                if !in_signature {
+                    expect_command = false
+                }
+            }
+        }
+        block_start = false;
+    }
+    res.push_str(&line[last..]);
+    res
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::lang::value::Value;
+
+    fn red(s: &str) -> String {
+        format!("{}{}{}", color::Fg(color::Red), s, color::Fg(color::Reset))
+    }
+
+    #[test]
+    fn unknown_commands() {
+        let scope = Scope::create_root();
+        scope.declare("known", Value::Integer(1)).unwrap();
+        assert_eq!(highlight("known unknown", &scope), "known unknown");
+        assert_eq!(
+            highlight("unknown known", &scope),
+            format!("{} known", red("unknown"))
+        );
+        assert!(!highlight("unknown := known", &scope).contains(&red("unknown")));
+        assert!(!highlight("known {|a b| other}", &scope).contains(&red("a")));
+        assert!(highlight("known {|a b| other}", &scope).contains(&red("other")));
+    }
+
+    #[test]
+    fn incomplete_input_is_kept() {
+        let scope = Scope::create_root();
+        let line = "known ^field \"unclosed";
+        assert!(highlight(line, &scope).ends_with(" \"unclosed"));
+    }
+}
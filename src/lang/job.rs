@@ -1,13 +1,28 @@
 use crate::lang::command_invocation::CommandInvocation;
-use crate::lang::errors::CrushResult;
+use crate::lang::errors::{argument_error, CrushResult};
 use crate::lang::execution_context::{CompileContext, JobContext};
 use crate::lang::printer::Printer;
+use crate::lang::scope::Scope;
 use crate::lang::stream::channels;
+use crate::lang::value::ValueType;
+use lazy_static::lazy_static;
+use std::fmt::{Display, Formatter};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex, MutexGuard};
 use std::thread::JoinHandle;
 
 pub enum JobJoinHandle {
     Many(Vec<JobJoinHandle>),
     Async(JoinHandle<()>),
+    Stopped(Stopped),
+}
+
+/** A job that was stopped using ^Z, and the ways to continue it. */
+pub struct Stopped {
+    /** Continue the job attached to the terminal, until it exits or is stopped again. */
+    pub foreground: Box<dyn FnOnce() + Send>,
+    /** Continue the job without the terminal, returning the handle to wait for it with. */
+    pub background: Box<dyn FnOnce() -> CrushResult<JobJoinHandle> + Send>,
 }
 
 impl JobJoinHandle {
@@ -22,6 +37,15 @@ impl JobJoinHandle {
                     j.join(printer);
                 }
             }
+            JobJoinHandle::Stopped(stopped) => (stopped.foreground)(),
+        }
+    }
+
+    pub fn is_finished(&self) -> bool {
+        match self {
+            JobJoinHandle::Async(a) => a.is_finished(),
+            JobJoinHandle::Many(v) => v.iter().all(|j| j.is_finished()),
+            JobJoinHandle::Stopped(_) => false,
         }
     }
 }
@@ -29,11 +53,36 @@ impl JobJoinHandle {
 #[derive(Clone)]
 pub struct Job {
     commands: Vec<CommandInvocation>,
+    background: bool,
+    source: Option<String>,
 }
 
 impl Job {
     pub fn new(commands: Vec<CommandInvocation>) -> Job {
-        Job { commands }
+        Job {
+            commands,
+            background: false,
+            source: None,
+        }
+    }
+
+    pub fn with_source(mut self, source: &str) -> Job {
+        self.source = Some(source.to_string());
+        self
+    }
+
+    pub fn in_background(mut self) -> Job {
+        self.background = true;
+        self
+    }
+
+    pub fn is_background(&self) -> bool {
+        self.background
+    }
+
+    /** The text this job was parsed from, if known. Falls back to a summary of the commands. */
+    pub fn source(&self) -> String {
+        self.source.clone().unwrap_or_else(|| self.to_string())
     }
 
     pub fn can_block(&self, context: &mut CompileContext) -> bool {
@@ -48,6 +97,18 @@ impl Job {
         &self.commands
     }
 
+    /**
+      Check that the commands of this job fit together, without running it. Returns the type of
+      the output of the job, if known.
+    */
+    pub fn check(&self, env: &Scope) -> CrushResult<Option<ValueType>> {
+        let mut res = None;
+        for call_def in &self.commands {
+            res = call_def.check(res.as_ref(), env)?;
+        }
+        Ok(res)
+    }
+
     pub fn invoke(&self, context: JobContext) -> CrushResult<JobJoinHandle> {
         let mut calls = Vec::new();
 
@@ -74,12 +135,204 @@ impl Job {
     }
 }
 
-impl ToString for Job {
-    fn to_string(&self) -> String {
-        self.commands
+impl Display for Job {
+    fn fmt(&self, f: &mut Formatter<'_>) -> Result<(), std::fmt::Error> {
+        f.write_str(
+            &self
+                .commands
+                .iter()
+                .map(|c| c.to_string())
+                .collect::<Vec<String>>()
+                .join("|"),
+        )
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum JobStatus {
+    Running,
+    Stopped,
+    Done,
+    Failed,
+}
+
+impl Display for JobStatus {
+    fn fmt(&self, f: &mut Formatter<'_>) -> Result<(), std::fmt::Error> {
+        f.write_str(match self {
+            JobStatus::Running => "running",
+            JobStatus::Stopped => "stopped",
+            JobStatus::Done => "done",
+            JobStatus::Failed => "failed",
+        })
+    }
+}
+
+struct BackgroundJob {
+    id: usize,
+    source: String,
+    handle: JobJoinHandle,
+    failed: Arc<AtomicBool>,
+}
+
+impl BackgroundJob {
+    fn status(&self) -> JobStatus {
+        if let JobJoinHandle::Stopped(_) = self.handle {
+            JobStatus::Stopped
+        } else if !self.handle.is_finished() {
+            JobStatus::Running
+        } else if self.failed.load(Ordering::Relaxed) {
+            JobStatus::Failed
+        } else {
+            JobStatus::Done
+        }
+    }
+}
+
+/**
+  The table of jobs that have been started in the background using a trailing `&`, or that were
+  stopped using ^Z.
+
+  Every job keeps the join handles of all the threads it spawned, so that the job can later be
+  waited for using `fg` or `wait`. Waiting for a stopped job continues it in the foreground. Jobs
+  that finish on their own are reported and removed by `report`, which the interactive loop calls
+  before showing the next prompt.
+*/
+pub struct JobTable {
+    jobs: Vec<BackgroundJob>,
+}
+
+lazy_static! {
+    static ref JOBS: Mutex<JobTable> = Mutex::new(JobTable { jobs: Vec::new() });
+}
+
+pub fn jobs() -> MutexGuard<'static, JobTable> {
+    JOBS.lock().unwrap()
+}
+
+impl JobTable {
+    pub fn add(&mut self, source: &str, handle: JobJoinHandle, failed: Arc<AtomicBool>) -> usize {
+        let id = self.jobs.iter().map(|j| j.id).max().unwrap_or(0) + 1;
+        self.jobs.push(BackgroundJob {
+            id,
+            source: source.to_string(),
+            handle,
+            failed,
+        });
+        id
+    }
+
+    /** Id, status and source of every job in the table. */
+    pub fn list(&self) -> Vec<(usize, JobStatus, String)> {
+        self.jobs
             .iter()
-            .map(|c| c.to_string())
-            .collect::<Vec<String>>()
-            .join("|")
+            .map(|j| (j.id, j.status(), j.source.clone()))
+            .collect()
+    }
+
+    /** Remove a job from the table, so that the caller can wait for it, by default the newest. */
+    pub fn take(&mut self, id: Option<usize>) -> CrushResult<(usize, String, JobJoinHandle)> {
+        let idx = match id {
+            None => match self.jobs.len() {
+                0 => return argument_error("No current job"),
+                len => len - 1,
+            },
+            Some(id) => match self.jobs.iter().position(|j| j.id == id) {
+                Some(idx) => idx,
+                None => return argument_error(format!("No such job: {}", id).as_str()),
+            },
+        };
+        let job = self.jobs.remove(idx);
+        Ok((job.id, job.source, job.handle))
+    }
+
+    pub fn ids(&self) -> Vec<usize> {
+        self.jobs.iter().map(|j| j.id).collect()
+    }
+
+    /** Continue a stopped job in the background. Defaults to the newest stopped job. */
+    pub fn resume(&mut self, id: Option<usize>) -> CrushResult<usize> {
+        let job = match id {
+            None => match self.jobs.iter_mut().rev().find(|j| j.status() == JobStatus::Stopped) {
+                Some(job) => job,
+                None => return argument_error("No stopped job"),
+            },
+            Some(id) => match self.jobs.iter_mut().find(|j| j.id == id) {
+                Some(job) => job,
+                None => return argument_error(format!("No such job: {}", id).as_str()),
+            },
+        };
+        let handle = std::mem::replace(&mut job.handle, JobJoinHandle::Many(Vec::new()));
+        job.handle = match handle {
+            JobJoinHandle::Stopped(stopped) => (stopped.background)()?,
+            handle => {
+                job.handle = handle;
+                return argument_error(format!("Job {} is not stopped", job.id).as_str());
+            }
+        };
+        Ok(job.id)
+    }
+
+    /** Print a line for every job that has finished since the last call, and forget about it. */
+    pub fn report(&mut self, printer: &Printer) {
+        let mut idx = 0;
+        while idx < self.jobs.len() {
+            let status = self.jobs[idx].status();
+            if status == JobStatus::Running || status == JobStatus::Stopped {
+                idx += 1;
+            } else {
+                let job = self.jobs.remove(idx);
+                printer.line(format_status(job.id, status, &job.source).as_str());
+                job.handle.join(printer);
+            }
+        }
+    }
+}
+
+pub fn format_status(id: usize, status: JobStatus, source: &str) -> String {
+    format!("[{}] {:<8}{}", id, status, source)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn finished_job() -> JobJoinHandle {
+        let h = std::thread::spawn(|| {});
+        while !h.is_finished() {
+            std::thread::yield_now();
+        }
+        JobJoinHandle::Async(h)
+    }
+
+    #[test]
+    fn job_table_ids() {
+        let mut table = JobTable { jobs: Vec::new() };
+        let no_failure = || Arc::new(AtomicBool::new(false));
+        assert_eq!(table.add("a", finished_job(), no_failure()), 1);
+        assert_eq!(table.add("b", finished_job(), no_failure()), 2);
+        assert_eq!(table.take(Some(1)).unwrap().1, "a");
+        assert!(table.take(Some(1)).is_err());
+        assert_eq!(table.add("c", finished_job(), no_failure()), 3);
+        assert_eq!(table.take(None).unwrap().1, "c");
+        assert!(table.list()[0].1 == JobStatus::Done);
+        assert_eq!(table.ids(), vec![2]);
+    }
+
+    #[test]
+    fn stopped_jobs_are_kept() {
+        let mut table = JobTable { jobs: Vec::new() };
+        let id = table.add(
+            "vi",
+            JobJoinHandle::Stopped(Stopped {
+                foreground: Box::new(|| {}),
+                background: Box::new(|| Ok(finished_job())),
+            }),
+            Arc::new(AtomicBool::new(false)),
+        );
+        table.report(&crate::lang::printer::init().0);
+        assert!(table.list()[0].1 == JobStatus::Stopped);
+        assert_eq!(table.resume(None).unwrap(), id);
+        assert!(table.list()[0].1 == JobStatus::Done);
+        assert!(table.resume(Some(id)).is_err());
     }
 }
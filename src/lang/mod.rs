@@ -3,12 +3,14 @@ pub mod ast;
 pub mod binary;
 pub mod command;
 pub mod command_invocation;
+pub mod completion;
 pub mod dict;
 pub mod errors;
 pub mod execute;
 pub mod execution_context;
 pub mod files;
 pub mod help;
+pub mod highlight;
 pub mod job;
 pub mod list;
 pub mod ordered_string_map;
@@ -21,4 +23,5 @@ pub mod serialization;
 pub mod stream;
 pub mod r#struct;
 pub mod table;
+pub mod typecheck;
 pub mod value;
@@ -1,6 +1,8 @@
-use crate::lang::errors::{to_crush_error, CrushResult};
+use crate::lang::ast::TokenNode;
+use crate::lang::errors::{parse_error, CrushResult};
 use crate::lang::job::Job;
 use crate::lang::scope::Scope;
+use lalrpop_util::ParseError;
 
 lalrpop_mod!(pub lalrparser, "/lang/lalrparser.rs");
 
@@ -15,5 +17,52 @@ pub fn parse_name(s: &str) -> Option<Vec<String>> {
 }
 
 pub fn parse(s: &str, env: &Scope) -> CrushResult<Vec<Job>> {
-    to_crush_error(lalrparser::JobListParser::new().parse(s))?.generate(env)
+    match lalrparser::JobListParser::new().parse(s) {
+        Ok(node) => node.generate(env),
+        Err(err) => parse_error(s, err),
+    }
+}
+
+/**
+  Returns true if the input ends before the last job is complete, e.g. because of an unclosed
+  brace, parenthesis or string, a trailing pipe or a trailing backslash.
+*/
+pub fn is_incomplete(s: &str) -> bool {
+    match lalrparser::JobListParser::new().parse(s) {
+        Err(ParseError::UnrecognizedEOF { .. }) => true,
+        Err(ParseError::InvalidToken { location }) => {
+            let rest = &s[location..];
+            rest.starts_with('"') || rest.starts_with('\'') || rest == "\\"
+        }
+        _ => false,
+    }
+}
+
+/**
+  Split the input into tokens. If the input contains something that isn't a valid token, only
+  the part before it is returned.
+*/
+pub fn tokenize(s: &str) -> Vec<TokenNode> {
+    match lalrparser::TokenListParser::new().parse(s) {
+        Ok(tokens) => tokens,
+        Err(ParseError::InvalidToken { location }) if location > 0 => tokenize(&s[..location]),
+        Err(_) => Vec::new(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn incomplete_input() {
+        assert!(is_incomplete("seq 3 | {"));
+        assert!(is_incomplete("seq 3 |"));
+        assert!(is_incomplete("echo (seq 3"));
+        assert!(is_incomplete("echo \"abc"));
+        assert!(is_incomplete("echo abc \\"));
+        assert!(!is_incomplete("echo abc"));
+        assert!(!is_incomplete("echo abc }"));
+        assert!(!is_incomplete(""));
+    }
 }
@@ -1,6 +1,6 @@
 use crate::lang::errors::{to_crush_error, CrushError, CrushResult, Kind};
-use crossbeam::bounded;
 use crossbeam::Sender;
+use crossbeam::{bounded, unbounded, Receiver};
 use std::thread;
 
 enum PrinterMessage {
@@ -11,26 +11,34 @@ enum PrinterMessage {
 }
 
 use crate::lang::printer::PrinterMessage::*;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
 use std::thread::JoinHandle;
 use termion::terminal_size;
 
 #[derive(Clone)]
 pub struct Printer {
     sender: Sender<PrinterMessage>,
+    failed: Option<Arc<AtomicBool>>,
+    capture: Option<Sender<CrushError>>,
 }
 
 pub fn init() -> (Printer, JoinHandle<()>) {
     let (sender, receiver) = bounded(128);
 
     (
-        Printer { sender: sender },
+        Printer {
+            sender: sender,
+            failed: None,
+            capture: None,
+        },
         thread::Builder::new()
             .name("printer".to_string())
             .spawn(move || {
                 while let Ok(message) = receiver.recv() {
                     match message {
                         Error(err) => eprintln!("Error: {}", err),
-                        CrushError(err) => eprintln!("Error: {}", err.message),
+                        CrushError(err) => eprint!("{}", format_error(&err)),
                         Line(line) => println!("{}", line),
                         //                        Lines(lines) => for line in lines {println!("{}", line)},
                     }
@@ -40,7 +48,87 @@ pub fn init() -> (Printer, JoinHandle<()>) {
     )
 }
 
+/**
+  Format an error for the user. If the error knows where in the source it happened, the offending
+  line is shown with the failing part underlined. Causes are shown after the error itself.
+*/
+fn format_error(err: &CrushError) -> String {
+    let mut res = match err.kind {
+        Kind::ParseError => format!("Parse error: {}\n", err.message),
+        _ => format!("Error: {}\n", err.message),
+    };
+    match (&err.command, &err.span) {
+        (Some(command), Some(span)) => res.push_str(&format!(
+            "  in {} at line {}, column {}\n",
+            command, span.line, span.column
+        )),
+        (Some(command), None) => res.push_str(&format!("  in {}\n", command)),
+        (None, Some(span)) => res.push_str(&format!(
+            "  at line {}, column {}\n",
+            span.line, span.column
+        )),
+        (None, None) => {}
+    }
+    if let Some(span) = &err.span {
+        let width = if span.end_line == span.line {
+            span.end_column.saturating_sub(span.column)
+        } else {
+            (span.text.chars().count() + 1).saturating_sub(span.column)
+        };
+        res.push_str(&format!(
+            "  | {}\n  | {}{}\n",
+            span.text,
+            " ".repeat(span.column - 1),
+            "^".repeat(width.max(1))
+        ));
+    }
+    if let Some(cause) = &err.cause {
+        res.push_str("Caused by: ");
+        res.push_str(&format_error(cause));
+    }
+    res
+}
+
 impl Printer {
+    /**
+    Returns a printer that writes to the same destination as this one, and a flag that is raised
+    as soon as a command reports a failure through it. Used to find out if a background job failed.
+    */
+    pub fn tracked(&self) -> (Printer, Arc<AtomicBool>) {
+        let failed = Arc::new(AtomicBool::new(false));
+        (
+            Printer {
+                sender: self.sender.clone(),
+                failed: Some(failed.clone()),
+                capture: self.capture.clone(),
+            },
+            failed,
+        )
+    }
+
+    /**
+    Returns a printer that sends errors to the returned receiver instead of printing them. Once
+    every clone of the printer has been dropped, the receiver is disconnected, which means that
+    every command that was given the printer has finished.
+    */
+    pub fn capturing(&self) -> (Printer, Receiver<CrushError>) {
+        let (sender, receiver) = unbounded();
+        (
+            Printer {
+                sender: self.sender.clone(),
+                failed: self.failed.clone(),
+                capture: Some(sender),
+            },
+            receiver,
+        )
+    }
+
+    fn mark_failed(&self) {
+        if let Some(failed) = &self.failed {
+            failed.store(true, Ordering::Relaxed);
+        }
+    }
+
     pub fn line(&self, line: &str) {
         self.handle_error(to_crush_error(
             self.sender.send(PrinterMessage::Line(line.to_string())),
@@ -61,9 +149,21 @@ impl Printer {
     }
 
     pub fn crush_error(&self, err: CrushError) {
+        self.mark_failed();
+        let err = match &self.capture {
+            Some(capture) => match capture.send(err) {
+                Ok(_) => return,
+                Err(e) => e.0,
+            },
+            None => err,
+        };
         let _ = self.sender.send(PrinterMessage::CrushError(err));
     }
 
+    /**
+    Print an error message, such as a line an external command wrote to stderr. Unlike crush_error,
+    this is not a failure, so the message is never captured and does not mark a tracked printer.
+    */
     pub fn error(&self, err: &str) {
         let _ = self.sender.send(PrinterMessage::Error(err.to_string()));
     }
@@ -1,5 +1,5 @@
-use crate::lang::command::{Command, CrushCommand, OutputType};
-use crate::lang::errors::{error, mandate, CrushResult};
+use crate::lang::command::{Command, CrushCommand, InputType, OutputType};
+use crate::lang::errors::{error, mandate, CrushError, CrushResult, Kind};
 use crate::lang::execution_context::ExecutionContext;
 use crate::lang::help::Help;
 use crate::lang::r#struct::Struct;
@@ -83,6 +83,18 @@ impl ScopeLoader {
         Ok(())
     }
 
+    /** Declare what the already declared command with the specified name expects as its input. */
+    pub fn declare_input(&mut self, name: &str, input: InputType) -> CrushResult<()> {
+        match self.mapping.get(name) {
+            Some(Value::Command(command)) => {
+                let command = command.with_input(input);
+                self.mapping.insert(name.to_string(), Value::Command(command));
+                Ok(())
+            }
+            _ => error(format!("Unknown command {}", name).as_str()),
+        }
+    }
+
     pub fn declare_condition_command(
         &mut self,
         name: &str,
@@ -338,13 +350,20 @@ impl Scope {
         }
         data.is_loaded = true;
         let loader = mandate(data.loader.take(), "Missing module loader")?;
+        let name = path.join(":");
         let mut tmp = ScopeLoader {
             mapping: OrderedMap::new(),
             path,
             parent: data.calling_scope.as_ref().unwrap().clone(),
             scope: self.clone(),
         };
-        loader(&mut tmp)?;
+        loader(&mut tmp).map_err(|e| {
+            CrushError::new(
+                Kind::GenericError,
+                format!("Failed to load namespace {}", name),
+            )
+            .with_cause(e)
+        })?;
         tmp.copy_into(&mut data.mapping);
         data.is_readonly = true;
 
@@ -570,7 +589,8 @@ impl Scope {
             p.dump(map)?;
         }
 
-        for u in self.data.lock().unwrap().uses.clone().iter().rev() {
+        let uses = self.data.lock().unwrap().uses.clone();
+        for u in uses.iter().rev() {
             u.dump(map)?;
         }
 
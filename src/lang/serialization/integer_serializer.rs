@@ -2,6 +2,7 @@ use crate::lang::errors::{error, to_crush_error, CrushResult};
 use crate::lang::serialization::model::{element, Element};
 use crate::lang::serialization::{DeserializationState, Serializable, SerializationState};
 use crate::lang::value::Value;
+use num_bigint::BigInt;
 use std::convert::TryFrom;
 
 impl Serializable<i128> for i128 {
@@ -33,3 +34,30 @@ impl Serializable<i128> for i128 {
         Ok(idx)
     }
 }
+
+impl Serializable<BigInt> for BigInt {
+    fn deserialize(
+        id: usize,
+        elements: &[Element],
+        _state: &mut DeserializationState,
+    ) -> CrushResult<BigInt> {
+        match elements[id].element.as_ref().unwrap() {
+            element::Element::SmallInteger(i) => Ok(BigInt::from(*i)),
+            element::Element::LargeInteger(s) => Ok(to_crush_error(s.parse())?),
+            _ => error("Expected integer"),
+        }
+    }
+
+    fn serialize(
+        &self,
+        elements: &mut Vec<Element>,
+        state: &mut SerializationState,
+    ) -> CrushResult<usize> {
+        let idx = elements.len();
+        state.values.insert(Value::from(self.clone()), idx);
+        elements.push(Element {
+            element: Some(element::Element::LargeInteger(self.to_string())),
+        });
+        Ok(idx)
+    }
+}
@@ -12,6 +12,7 @@ use crate::lang::value::{Value, ValueType};
 use crate::util::glob::Glob;
 use chrono::offset::TimeZone;
 use chrono::{Duration, Local};
+use num_bigint::BigInt;
 use regex::Regex;
 use std::ffi::OsStr;
 use std::os::unix::ffi::OsStrExt;
@@ -64,7 +65,7 @@ impl Serializable<Value> for Value {
             element::Element::Empty(_) => Ok(Value::Empty()),
 
             element::Element::SmallInteger(_) | element::Element::LargeInteger(_) => {
-                Ok(Value::Integer(i128::deserialize(id, elements, state)?))
+                Ok(Value::from(BigInt::deserialize(id, elements, state)?))
             }
 
             element::Element::Duration(d) => Ok(Value::Duration(
@@ -123,6 +124,7 @@ impl Serializable<Value> for Value {
             | Value::Field(_) => serialize_simple(self, elements, state),
 
             Value::Integer(s) => s.serialize(elements, state),
+            Value::BigInteger(s) => s.serialize(elements, state),
 
             Value::Duration(d) => {
                 let mut node = Element::default();
@@ -144,6 +146,7 @@ impl Serializable<Value> for Value {
             Value::Dict(d) => d.serialize(elements, state),
             Value::Scope(s) => s.serialize(elements, state),
             Value::TableStream(_) | Value::BinaryStream(_) => error("Can't serialize streams"),
+            Value::Error(_) => error("Can't serialize errors"),
         }
     }
 }
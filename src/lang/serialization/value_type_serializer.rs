@@ -33,6 +33,7 @@ impl Serializable<ValueType> for ValueType {
                     14 => ValueType::Time,
                     15 => ValueType::Struct,
                     16 => ValueType::Any,
+                    18 => ValueType::Error,
                     _ => return error("Unrecognised type"),
                 }),
                 model::r#type::Type::ListType(l) => Ok(ValueType::List(Box::from(
@@ -147,6 +148,7 @@ impl Serializable<ValueType> for ValueType {
                 return Ok(idx);
             }
             ValueType::BinaryStream => SimpleTypeKind::BinaryStream,
+            ValueType::Error => SimpleTypeKind::Error,
         };
 
         let idx = elements.len();
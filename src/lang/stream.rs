@@ -22,9 +22,25 @@ pub fn black_hole() -> ValueSender {
 #[derive(Clone)]
 pub struct ValueSender {
     sender: Sender<Value>,
+    tty: bool,
 }
 
 impl ValueSender {
+    /**
+      Mark this sender as leading straight to the terminal. External commands that output to such
+      a sender are run on a pty, so that interactive programs work.
+    */
+    pub fn tty(&self) -> ValueSender {
+        ValueSender {
+            sender: self.sender.clone(),
+            tty: true,
+        }
+    }
+
+    pub fn is_tty(&self) -> bool {
+        self.tty
+    }
+
     pub fn send(&self, cell: Value) -> CrushResult<()> {
         match self.sender.send(cell) {
             Ok(_) => Ok(()),
@@ -61,10 +77,9 @@ pub struct OutputStream {
 
 impl OutputStream {
     pub fn send(&self, row: Row) -> CrushResult<()> {
-        let native_output = self.sender.send(row);
-        match native_output {
+        match self.sender.send(row) {
             Ok(_) => Ok(()),
-            Err(e) => error(e.to_string()),
+            Err(_) => send_error(),
         }
     }
 }
@@ -132,7 +147,10 @@ impl InputStream {
 pub fn channels() -> (ValueSender, ValueReceiver) {
     let (send, recv) = bounded(1);
     (
-        ValueSender { sender: send },
+        ValueSender {
+            sender: send,
+            tty: false,
+        },
         ValueReceiver { receiver: recv },
     )
 }
@@ -0,0 +1,160 @@
+use crate::lang::argument::{ArgumentDefinition, ArgumentType};
+use crate::lang::command::{InputType, OutputType};
+use crate::lang::errors::{argument_error, CrushResult};
+use crate::lang::scope::Scope;
+use crate::lang::table::{ColumnType, ColumnVec};
+use crate::lang::value::{Value, ValueDefinition, ValueType};
+
+/** Find the value a command definition refers to, without invoking anything. */
+fn resolve(definition: &ValueDefinition, env: &Scope) -> Option<Value> {
+    match definition {
+        ValueDefinition::Value(value) => Some(value.clone()),
+        ValueDefinition::Label(name) => env.get(name).ok()?,
+        ValueDefinition::GetAttr(parent, name) => resolve(parent, env)?.field(name).ok()?,
+        _ => None,
+    }
+}
+
+/** The columns of a value of the specified type when read as a stream. */
+fn columns(input: &ValueType) -> Option<Vec<ColumnType>> {
+    match input {
+        ValueType::TableStream(types) | ValueType::Table(types) => Some(types.clone()),
+        ValueType::List(element_type) => Some(vec![ColumnType::new(
+            "value",
+            element_type.as_ref().clone(),
+        )]),
+        ValueType::Dict(key_type, value_type) => Some(vec![
+            ColumnType::new("key", key_type.as_ref().clone()),
+            ColumnType::new("value", value_type.as_ref().clone()),
+        ]),
+        _ => None,
+    }
+}
+
+/** The unnamed field arguments, which all refer to columns of the input. */
+fn fields(arguments: &[ArgumentDefinition]) -> Vec<&str> {
+    arguments
+        .iter()
+        .filter_map(|a| match (&a.argument_type, &a.value) {
+            (ArgumentType::None, ValueDefinition::Value(Value::Field(f))) if f.len() == 1 => {
+                Some(f[0].as_str())
+            }
+            _ => None,
+        })
+        .collect()
+}
+
+fn check_column(
+    name: &str,
+    types: &[ColumnType],
+    arguments: &[ArgumentDefinition],
+    allowed: &[ValueType],
+) -> CrushResult<()> {
+    let idx = match fields(arguments).first() {
+        Some(field) => types.find_str(field)?,
+        None if types.len() == 1 => 0,
+        None => return Ok(()),
+    };
+    let column = &types[idx];
+    if column.cell_type == ValueType::Any || allowed.contains(&column.cell_type) {
+        Ok(())
+    } else {
+        argument_error(
+            format!(
+                "{} can't be used on column {} of type {}, expected one of {}",
+                name,
+                column.name,
+                column.cell_type.to_string(),
+                allowed
+                    .iter()
+                    .map(|t| t.to_string())
+                    .collect::<Vec<_>>()
+                    .join(", ")
+            )
+            .as_str(),
+        )
+    }
+}
+
+fn check_input(
+    name: &str,
+    expected: InputType,
+    arguments: &[ArgumentDefinition],
+    input: &ValueType,
+) -> CrushResult<()> {
+    if *input == ValueType::Any {
+        return Ok(());
+    }
+    match expected {
+        InputType::Unknown => Ok(()),
+        InputType::Table | InputType::Column(_) => match columns(input) {
+            Some(types) => {
+                for field in fields(arguments) {
+                    types.as_slice().find_str(field)?;
+                }
+                match expected {
+                    InputType::Column(allowed) => check_column(name, &types, arguments, &allowed),
+                    _ => Ok(()),
+                }
+            }
+            None => argument_error(
+                format!(
+                    "{} expects a table or a stream as input, got a value of type {}",
+                    name,
+                    input.to_string()
+                )
+                .as_str(),
+            ),
+        },
+        InputType::Binary => {
+            let has_files = arguments.iter().any(|a| {
+                matches!(
+                    a.argument_type,
+                    ArgumentType::None | ArgumentType::ArgumentList
+                )
+            });
+            match input {
+                ValueType::Binary | ValueType::BinaryStream => Ok(()),
+                _ if has_files => Ok(()),
+                _ => argument_error(
+                    format!(
+                        "{} expects binary input or a file to read, got a value of type {}",
+                        name,
+                        input.to_string()
+                    )
+                    .as_str(),
+                ),
+            }
+        }
+    }
+}
+
+/**
+  Check a single invocation against the type of its input, if known, and return the type of its
+  output, if known.
+
+  The command is looked up without running anything. When both the type of the input and what
+  the command expects as its input are known, they are checked against each other, so that e.g.
+  `ps | sort ^nosuchcol` fails before ps has been started. Anything that can't be known
+  statically, like the output of external commands and of commands that don't declare their
+  output type, is not checked.
+*/
+pub fn invocation(
+    command: &ValueDefinition,
+    arguments: &[ArgumentDefinition],
+    input: Option<&ValueType>,
+    env: &Scope,
+) -> CrushResult<Option<ValueType>> {
+    let cmd = match resolve(command, env) {
+        Some(Value::Command(cmd)) => cmd,
+        _ => return Ok(None),
+    };
+    let input_type = match input {
+        Some(input) => {
+            check_input(&command.to_string(), cmd.input(), arguments, input)?;
+            OutputType::Known(input.clone())
+        }
+        None => OutputType::Unknown,
+    };
+    Ok(cmd.output(&input_type).cloned())
+}
@@ -9,7 +9,7 @@ use std::str::FromStr;
 use chrono::{DateTime, Local};
 use regex::Regex;
 
-use crate::lang::errors::{argument_error, mandate, CrushResult};
+use crate::lang::errors::{argument_error, mandate, CrushError, CrushResult, ERROR_FIELDS};
 use crate::lang::r#struct::Struct;
 use crate::lang::scope::Scope;
 use crate::lang::stream::{streams, InputStream, Stream};
@@ -25,6 +25,8 @@ use crate::{
     util::glob::Glob,
 };
 use chrono::Duration;
+use num_bigint::BigInt;
+use num_traits::{FromPrimitive, ToPrimitive};
 
 use crate::lang::command::Command;
 use crate::lang::help::Help;
@@ -40,6 +42,8 @@ pub type Field = Vec<String>;
 pub enum Value {
     String(String),
     Integer(i128),
+    /** An integer that is too large for an i128. Create these using Value::from, never directly. */
+    BigInteger(BigInt),
     Time(DateTime<Local>),
     Duration(Duration),
     Field(Field),
@@ -59,6 +63,7 @@ pub enum Value {
     BinaryStream(Box<dyn BinaryReader + Send + Sync>),
     Binary(Vec<u8>),
     Type(ValueType),
+    Error(CrushError),
 }
 
 impl ToString for Value {
@@ -66,6 +71,7 @@ impl ToString for Value {
         match self {
             Value::String(val) => val.to_string(),
             Value::Integer(val) => val.to_string(),
+            Value::BigInteger(val) => val.to_string(),
             Value::Time(val) => val.format("%Y-%m-%d %H:%M:%S %z").to_string(),
             Value::Field(val) => format!(r"^{}", val.join(":")),
             Value::Glob(val) => val.to_string(),
@@ -80,6 +86,7 @@ impl ToString for Value {
             Value::Binary(v) => format_buffer(v, true),
             Value::Type(t) => t.to_string(),
             Value::Struct(s) => s.to_string(),
+            Value::Error(e) => e.message.clone(),
             _ => format!("<{}>", self.value_type().to_string()),
         }
     }
@@ -110,6 +117,7 @@ impl Value {
                 .fields()
                 .get(name)
                 .map(|m| Value::Command(m.as_ref().copy())),
+            Value::Error(e) => e.field(name),
             _ => self
                 .value_type()
                 .fields()
@@ -124,6 +132,7 @@ impl Value {
             Value::Struct(s) => res.append(&mut s.keys()),
             //            Value::Scope(subenv) => subenv.get(name),
             Value::Type(t) => add_keys(t.fields(), &mut res),
+            Value::Error(_) => res.append(&mut ERROR_FIELDS.iter().map(|f| f.to_string()).collect()),
             _ => add_keys(self.value_type().fields(), &mut res),
         }
         res.sort_by(|x, y| x.cmp(y));
@@ -149,7 +158,9 @@ impl Value {
 
     pub fn alignment(&self) -> Alignment {
         match self {
-            Value::Time(_) | Value::Duration(_) | Value::Integer(_) => Alignment::Right,
+            Value::Time(_) | Value::Duration(_) | Value::Integer(_) | Value::BigInteger(_) => {
+                Alignment::Right
+            }
             _ => Alignment::Left,
         }
     }
@@ -163,6 +174,23 @@ impl Value {
         Value::String(s.to_string())
     }
 
+    /** Parse an integer of any size. */
+    pub fn parse_integer(s: &str) -> CrushResult<Value> {
+        match s.parse::<i128>() {
+            Ok(i) => Ok(Value::Integer(i)),
+            Err(_) => Ok(Value::from(to_crush_error(s.parse::<BigInt>())?)),
+        }
+    }
+
+    /** Returns the value as a BigInt, if it is an integer of any size. */
+    pub fn big_integer(&self) -> Option<BigInt> {
+        match self {
+            Value::Integer(i) => Some(BigInt::from(*i)),
+            Value::BigInteger(i) => Some(i.clone()),
+            _ => None,
+        }
+    }
+
     pub fn stream(&self) -> Option<Stream> {
         match self {
             Value::TableStream(s) => Some(Box::from(s.clone())),
@@ -176,7 +204,7 @@ impl Value {
     pub fn value_type(&self) -> ValueType {
         match self {
             Value::String(_) => ValueType::String,
-            Value::Integer(_) => ValueType::Integer,
+            Value::Integer(_) | Value::BigInteger(_) => ValueType::Integer,
             Value::Time(_) => ValueType::Time,
             Value::Field(_) => ValueType::Field,
             Value::Glob(_) => ValueType::Glob,
@@ -196,6 +224,7 @@ impl Value {
             Value::BinaryStream(_) => ValueType::BinaryStream,
             Value::Binary(_) => ValueType::Binary,
             Value::Type(_) => ValueType::Type,
+            Value::Error(_) => ValueType::Error,
         }
     }
 
@@ -262,7 +291,13 @@ impl Value {
 
         match (&self, &new_type) {
             (Value::Integer(i), ValueType::Bool) => return Ok(Value::Bool(*i != 0)),
-            (Value::Float(f), ValueType::Integer) => return Ok(Value::Integer(*f as i128)),
+            (Value::BigInteger(_), ValueType::Bool) => return Ok(Value::Bool(true)),
+            (Value::Float(f), ValueType::Integer) => {
+                return Ok(Value::from(mandate(
+                    BigInt::from_f64(*f),
+                    "Can't convert value to integer",
+                )?))
+            }
             _ => {}
         }
 
@@ -271,7 +306,7 @@ impl Value {
         match new_type {
             ValueType::File => Ok(Value::File(PathBuf::from(str_val.as_str()))),
             ValueType::Glob => Ok(Value::Glob(Glob::new(str_val.as_str()))),
-            ValueType::Integer => to_crush_error(str_val.parse::<i128>()).map(Value::Integer),
+            ValueType::Integer => Value::parse_integer(&str_val),
             ValueType::Field => Ok(Value::Field(vec![str_val])),
             ValueType::Regex => {
                 to_crush_error(Regex::new(str_val.as_str()).map(|v| Value::Regex(str_val, v)))
@@ -301,6 +336,16 @@ impl Value {
             ValueType::Any => error("Invalid convert"),
             ValueType::BinaryStream => error("invalid convert"),
             ValueType::Type => error("invalid convert"),
+            ValueType::Error => error("invalid convert"),
+        }
+    }
+}
+
+impl From<BigInt> for Value {
+    fn from(i: BigInt) -> Value {
+        match i.to_i128() {
+            Some(small) => Value::Integer(small),
+            None => Value::BigInteger(i),
         }
     }
 }
@@ -310,6 +355,7 @@ impl Clone for Value {
         match self {
             Value::String(v) => Value::String(v.clone()),
             Value::Integer(v) => Value::Integer(*v),
+            Value::BigInteger(v) => Value::BigInteger(v.clone()),
             Value::Time(v) => Value::Time(*v),
             Value::Field(v) => Value::Field(v.clone()),
             Value::Glob(v) => Value::Glob(v.clone()),
@@ -329,6 +375,7 @@ impl Clone for Value {
             Value::BinaryStream(v) => Value::BinaryStream(v.as_ref().clone()),
             Value::Binary(v) => Value::Binary(v.clone()),
             Value::Type(t) => Value::Type(t.clone()),
+            Value::Error(e) => Value::Error(e.clone()),
         }
     }
 }
@@ -355,6 +402,7 @@ impl std::hash::Hash for Value {
         match self {
             Value::String(v) => v.hash(state),
             Value::Integer(v) => v.hash(state),
+            Value::BigInteger(v) => v.hash(state),
             Value::Time(v) => v.hash(state),
             Value::Field(v) => v.hash(state),
             Value::Glob(v) => v.hash(state),
@@ -379,6 +427,7 @@ impl std::hash::Hash for Value {
             }
             Value::Empty() => {}
             Value::Type(v) => v.to_string().hash(state),
+            Value::Error(e) => e.message.hash(state),
         }
     }
 }
@@ -395,6 +444,7 @@ impl std::cmp::PartialEq for Value {
         match (self, other) {
             (Value::String(val1), Value::String(val2)) => val1 == val2,
             (Value::Integer(val1), Value::Integer(val2)) => val1 == val2,
+            (Value::BigInteger(val1), Value::BigInteger(val2)) => val1 == val2,
             (Value::Time(val1), Value::Time(val2)) => val1 == val2,
             (Value::Duration(val1), Value::Duration(val2)) => val1 == val2,
             (Value::Field(val1), Value::Field(val2)) => val1 == val2,
@@ -434,6 +484,9 @@ impl std::cmp::PartialOrd for Value {
         match (self, other) {
             (Value::String(val1), Value::String(val2)) => Some(val1.cmp(val2)),
             (Value::Integer(val1), Value::Integer(val2)) => Some(val1.cmp(val2)),
+            (Value::BigInteger(val1), Value::BigInteger(val2)) => Some(val1.cmp(val2)),
+            (Value::Integer(val1), Value::BigInteger(val2)) => Some(BigInt::from(*val1).cmp(val2)),
+            (Value::BigInteger(val1), Value::Integer(val2)) => Some(val1.cmp(&BigInt::from(*val2))),
             (Value::Time(val1), Value::Time(val2)) => Some(val1.cmp(val2)),
             (Value::Duration(val1), Value::Duration(val2)) => Some(val1.cmp(val2)),
             (Value::Field(val1), Value::Field(val2)) => Some(val1.cmp(val2)),
@@ -503,6 +556,21 @@ mod tests {
         );
     }
 
+    #[test]
+    fn big_integers_are_normalized() {
+        let small = Value::from(BigInt::from(17));
+        assert!(matches!(small, Value::Integer(17)));
+        let big = Value::parse_integer("1000000000000000000000000000000000000000000").unwrap();
+        assert!(matches!(big, Value::BigInteger(_)));
+        assert_eq!(big.value_type(), ValueType::Integer);
+        assert_eq!(
+            big.to_string(),
+            "1000000000000000000000000000000000000000000"
+        );
+        assert_eq!(small.partial_cmp(&big), Some(Ordering::Less));
+        assert_eq!(big.partial_cmp(&small), Some(Ordering::Greater));
+    }
+
     #[test]
     fn test_duration_format() {
         assert_eq!(duration_format(&Duration::microseconds(0)), "0".to_string());
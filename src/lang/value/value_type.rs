@@ -5,6 +5,7 @@ use crate::lang::parser::parse_name;
 use crate::lang::{table::ColumnType, value::Value};
 use crate::lib::types;
 use crate::util::glob::Glob;
+use chrono::{DateTime, Local, TimeZone};
 use lazy_static::lazy_static;
 use ordered_map::OrderedMap;
 use regex::Regex;
@@ -34,6 +35,7 @@ pub enum ValueType {
     BinaryStream,
     Binary,
     Type,
+    Error,
 }
 
 lazy_static! {
@@ -82,6 +84,7 @@ impl ValueType {
             | ValueType::Any
             | ValueType::Binary
             | ValueType::Type
+            | ValueType::Error
             | ValueType::Struct
             | ValueType::Bool => self.clone(),
             ValueType::BinaryStream => ValueType::Binary,
@@ -115,21 +118,31 @@ impl ValueType {
     pub fn parse(&self, s: &str) -> CrushResult<Value> {
         match self {
             ValueType::String => Ok(Value::string(s)),
-            ValueType::Integer => match s.parse::<i128>() {
-                Ok(n) => Ok(Value::Integer(n)),
-                Err(e) => error(e.to_string().as_str()),
-            },
+            ValueType::Integer => Value::parse_integer(s),
             ValueType::Field => Ok(Value::Field(mandate(parse_name(s), "Invalid field name")?)),
             ValueType::Glob => Ok(Value::Glob(Glob::new(s))),
             ValueType::Regex => Ok(Value::Regex(s.to_string(), to_crush_error(Regex::new(s))?)),
             ValueType::File => Ok(Value::string(s)),
             ValueType::Float => Ok(Value::Float(to_crush_error(s.parse::<f64>())?)),
             ValueType::Bool => Ok(Value::Bool(to_crush_error(s.parse::<bool>())?)),
+            ValueType::Time => parse_time(s),
             _ => error("Failed to parse cell"),
         }
     }
 }
 
+/**
+  Parse a time in RFC 3339 format, in the format times are displayed in, or without a time zone,
+  in which case it is taken to be local time.
+*/
+fn parse_time(s: &str) -> CrushResult<Value> {
+    let res = DateTime::parse_from_rfc3339(s)
+        .or_else(|_| DateTime::parse_from_str(s, "%Y-%m-%d %H:%M:%S %z"))
+        .map(|t| t.with_timezone(&Local))
+        .or_else(|_| Local.datetime_from_str(s, "%Y-%m-%d %H:%M:%S"));
+    Ok(Value::Time(to_crush_error(res)?))
+}
+
 impl Help for ValueType {
     fn signature(&self) -> String {
         format!("type {}", self.to_string())
@@ -163,6 +176,7 @@ impl Help for ValueType {
             ValueType::BinaryStream => "A stream of binary data",
             ValueType::Binary => "Binary data",
             ValueType::Type => "A type",
+            ValueType::Error => "An error raised by a failing command",
         }
         .to_string()
     }
@@ -227,6 +241,7 @@ impl ToString for ValueType {
             ValueType::Float => "float".to_string(),
             ValueType::Empty => "empty".to_string(),
             ValueType::Any => "any".to_string(),
+            ValueType::Error => "error".to_string(),
             ValueType::BinaryStream => "binary_stream".to_string(),
             ValueType::Binary => "binary".to_string(),
             ValueType::Type => "type".to_string(),
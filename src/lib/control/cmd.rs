@@ -0,0 +1,411 @@
+use crate::lang::argument::{Argument, ArgumentHandler};
+use crate::lang::binary::BinaryReader;
+use crate::lang::command::OutputType::Known;
+use crate::lang::command_invocation::resolve_external_command;
+use crate::lang::errors::{argument_error, error, mandate, to_crush_error, CrushResult};
+use crate::lang::execution_context::{ArgumentVector, ExecutionContext};
+use crate::lang::list::List;
+use crate::lang::r#struct::Struct;
+use crate::lang::scope::Scope;
+use crate::lang::value::{Value, ValueType};
+use crate::util::file::cwd;
+use crate::util::pty;
+use crate::util::thread::build;
+use chrono::Duration;
+use nix::sys::signal::{killpg, Signal};
+use nix::unistd::Pid;
+use signature::signature;
+use std::io::{BufRead, BufReader, Read};
+use std::os::unix::process::{CommandExt, ExitStatusExt};
+use std::path::PathBuf;
+use std::process::{Child, ChildStderr, ChildStdout, Command, ExitStatus, Stdio};
+use std::thread::JoinHandle;
+use std::time::Instant;
+
+/** How a named argument with a value is turned into a long option. Short options are always separate. */
+enum OptionStyle {
+    /** `--name value` */
+    Separate,
+    /** `--name=value` */
+    Equals,
+}
+
+impl OptionStyle {
+    fn lookup(context: &ExecutionContext) -> CrushResult<OptionStyle> {
+        match context.env.get("cmd_option_style")? {
+            None => Ok(OptionStyle::Separate),
+            Some(Value::String(s)) => match s.as_str() {
+                "separate" => Ok(OptionStyle::Separate),
+                "equals" => Ok(OptionStyle::Equals),
+                _ => argument_error(
+                    format!(
+                        "Invalid cmd_option_style {}, expected separate or equals",
+                        s
+                    )
+                    .as_str(),
+                ),
+            },
+            Some(_) => argument_error("Expected cmd_option_style to be a string"),
+        }
+    }
+}
+
+/**
+  Expand a value into the arguments it represents. Lists are expanded element by element and
+  globs into the files they match, in sorted order. All other values, including strings that start
+  with a hyphen, become exactly one argument.
+*/
+fn expand(value: Value, res: &mut Vec<String>) -> CrushResult<()> {
+    match value {
+        Value::List(l) => {
+            for v in l.dump() {
+                expand(v, res)?;
+            }
+        }
+        Value::Glob(g) => {
+            let mut files = Vec::new();
+            g.glob_files(&cwd()?, &mut files)?;
+            if files.is_empty() {
+                return argument_error(
+                    format!("The glob {} does not match any files", g.to_string()).as_str(),
+                );
+            }
+            files.sort();
+            for f in files {
+                res.push(mandate(f.to_str(), "Invalid filename")?.to_string());
+            }
+        }
+        Value::File(f) => res.push(mandate(f.to_str(), "Invalid filename")?.to_string()),
+        v => res.push(v.to_string()),
+    }
+    Ok(())
+}
+
+fn option(name: &str) -> String {
+    if name.len() == 1 {
+        format!("-{}", name)
+    } else {
+        format!("--{}", name)
+    }
+}
+
+/**
+  Turn a named argument into options. Boolean values turn the option on or leave it out, lists
+  repeat the option once per element and other values are passed on as the value of the option.
+*/
+fn expand_named(
+    name: &str,
+    value: Value,
+    style: &OptionStyle,
+    res: &mut Vec<String>,
+) -> CrushResult<()> {
+    match value {
+        Value::Bool(true) => res.push(option(name)),
+        Value::Bool(false) => {}
+        Value::List(l) => {
+            for v in l.dump() {
+                expand_named(name, v, style, res)?;
+            }
+        }
+        v => {
+            let mut values = Vec::new();
+            expand(v, &mut values)?;
+            for value in values {
+                match style {
+                    OptionStyle::Equals if name.len() > 1 => {
+                        res.push(format!("{}={}", option(name), value))
+                    }
+                    _ => {
+                        res.push(option(name));
+                        res.push(value);
+                    }
+                }
+            }
+        }
+    }
+    Ok(())
+}
+
+fn argv(arguments: Vec<Argument>, style: &OptionStyle) -> CrushResult<Vec<String>> {
+    let mut res = Vec::new();
+    for a in arguments {
+        match a.argument_type {
+            None => expand(a.value, &mut res)?,
+            Some(name) => expand_named(&name, a.value, style, &mut res)?,
+        }
+    }
+    Ok(res)
+}
+
+/** Find the file to execute for a command given as a file or as a name to look up in cmd_path. */
+fn resolve(command: Value, env: &Scope) -> CrushResult<PathBuf> {
+    match command {
+        Value::File(f) => Ok(f),
+        Value::String(s) => mandate(
+            resolve_external_command(&s, env)?,
+            format!("Could not find {} in cmd_path", s).as_str(),
+        ),
+        _ => argument_error("Expected the command to be a file or a string"),
+    }
+}
+
+pub fn cmd(mut context: ExecutionContext) -> CrushResult<()> {
+    if context.arguments.is_empty() {
+        return argument_error("No command given");
+    }
+    let style = OptionStyle::lookup(&context)?;
+    let path = resolve(context.arguments.remove(0).value, &context.env)?;
+    let mut cmd = Command::new(path);
+    cmd.args(argv(context.arguments, &style)?);
+    if context.output.is_tty() && pty::is_available() {
+        pty::run(cmd, &context.printer)?;
+        return context.output.send(Value::Empty());
+    }
+    cmd.stdin(Stdio::null())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped());
+    let mut child = to_crush_error(cmd.spawn())?;
+    let stdout = mandate(child.stdout.take(), "Missing output of command")?;
+    let stderr = mandate(child.stderr.take(), "Missing error output of command")?;
+    let printer = context.printer.clone();
+    let errors = to_crush_error(build("cmd:stderr").spawn(move || {
+        for line in BufReader::new(stderr).lines() {
+            match line {
+                Ok(line) => {
+                    let err = line.trim();
+                    if !err.is_empty() {
+                        printer.error(err);
+                    }
+                }
+                Err(_) => break,
+            }
+        }
+    }))?;
+    // Send the output right away, so that the next command can consume it while it is produced
+    context
+        .output
+        .send(Value::BinaryStream(BinaryReader::pipe(stdout)))?;
+    to_crush_error(child.wait())?;
+    let _ = errors.join();
+    Ok(())
+}
+
+pub fn which(mut context: ExecutionContext) -> CrushResult<()> {
+    context.arguments.check_len(1)?;
+    let name = context.arguments.string(0)?;
+    match resolve_external_command(&name, &context.env)? {
+        Some(path) => context.output.send(Value::File(path)),
+        None => error(format!("Could not find {} in cmd_path", name).as_str()),
+    }
+}
+
+#[signature(
+    run,
+    can_block = true,
+    output = Known(ValueType::Struct),
+    short = "Run an external command and return its exit status and output",
+    long = "Unlike cmd, run does not fail or print anything when the command fails. Unnamed arguments are expanded the same way cmd expands them.",
+    long = "Return a struct with the following fields:",
+    long = "* status:integer, the exit status of the command, or 128 plus the signal that killed it",
+    long = "* stdout:binary_stream, the output of the command",
+    long = "* stderr:binary_stream or list, the error output of the command",
+    long = "* timed_out:bool, true if the command was killed because it did not exit in time",
+    long = "With a timeout, the command is run in a process group of its own, and the whole group is killed when the time is up. The output produced until then is kept.",
+    example = "run \"make\" \"test\" cwd=\"build\" timeout=(duration:new minutes=10) | member ^status"
+)]
+pub struct Run {
+    #[description("the command to run, a file or a name to look up in cmd_path.")]
+    command: Value,
+    #[unnamed()]
+    #[description("the arguments to pass to the command.")]
+    argument: Vec<Value>,
+    #[description("the working directory of the command.")]
+    cwd: Option<Value>,
+    #[description("a dict of environment variables to set for the command.")]
+    env: Option<Value>,
+    #[description("kill the command if it does not exit in time.")]
+    timeout: Option<Duration>,
+    #[description("return stderr as a binary stream or as a list of lines.")]
+    #[values("binary", "lines")]
+    #[default("binary")]
+    stderr: String,
+}
+
+fn read_all<R: Read + Send + 'static>(
+    name: &str,
+    reader: Option<R>,
+) -> CrushResult<JoinHandle<Vec<u8>>> {
+    to_crush_error(build(name).spawn(move || {
+        let mut buf = Vec::new();
+        if let Some(mut reader) = reader {
+            let _ = reader.read_to_end(&mut buf);
+        }
+        buf
+    }))
+}
+
+fn join(handle: JoinHandle<Vec<u8>>) -> CrushResult<Vec<u8>> {
+    match handle.join() {
+        Ok(buf) => Ok(buf),
+        Err(_) => error("Failed to read the output of the command"),
+    }
+}
+
+/**
+  Wait for the command to exit, and whether it had to be killed because it ran out of time.
+
+  A command with a timeout is the leader of its own process group. Killing the whole group also
+  kills any processes it started that still hold on to its output, so that the threads reading
+  the output always finish.
+*/
+fn wait(child: &mut Child, timeout: Option<Duration>) -> CrushResult<(ExitStatus, bool)> {
+    match timeout {
+        None => Ok((to_crush_error(child.wait())?, false)),
+        Some(timeout) => {
+            let deadline = Instant::now() + to_crush_error(timeout.to_std())?;
+            loop {
+                if let Some(status) = to_crush_error(child.try_wait())? {
+                    return Ok((status, false));
+                }
+                if Instant::now() >= deadline {
+                    let _ = killpg(Pid::from_raw(child.id() as i32), Signal::SIGKILL);
+                    return Ok((to_crush_error(child.wait())?, true));
+                }
+                std::thread::sleep(std::time::Duration::from_millis(10));
+            }
+        }
+    }
+}
+
+pub fn run(context: ExecutionContext) -> CrushResult<()> {
+    let cfg: Run = Run::parse(context.arguments, &context.printer)?;
+    let path = resolve(cfg.command, &context.env)?;
+    let mut arguments = Vec::new();
+    for a in cfg.argument {
+        expand(a, &mut arguments)?;
+    }
+
+    let mut cmd = Command::new(path);
+    cmd.args(arguments)
+        .stdin(Stdio::null())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped());
+    match cfg.cwd {
+        None => {}
+        Some(Value::File(f)) => {
+            cmd.current_dir(f);
+        }
+        Some(Value::String(s)) => {
+            cmd.current_dir(s.as_ref() as &str);
+        }
+        Some(_) => return argument_error("Expected cwd to be a file or a string"),
+    }
+    match cfg.env {
+        None => {}
+        Some(Value::Dict(d)) => {
+            for (key, value) in d.elements() {
+                cmd.env(key.to_string(), value.to_string());
+            }
+        }
+        Some(_) => return argument_error("Expected env to be a dict"),
+    }
+    if cfg.timeout.is_some() {
+        cmd.process_group(0);
+    }
+
+    let mut child = to_crush_error(cmd.spawn())?;
+    let stdout = read_all::<ChildStdout>("run:stdout", child.stdout.take())?;
+    let stderr = read_all::<ChildStderr>("run:stderr", child.stderr.take())?;
+    let waited = wait(&mut child, cfg.timeout);
+    // Always join the readers, which finish once every process writing to the pipes is gone
+    let stdout = join(stdout)?;
+    let stderr = join(stderr)?;
+    let (status, timed_out) = waited?;
+
+    let stderr = match cfg.stderr.as_str() {
+        "lines" => Value::List(List::new(
+            ValueType::String,
+            String::from_utf8_lossy(&stderr)
+                .lines()
+                .map(Value::string)
+                .collect(),
+        )),
+        _ => Value::BinaryStream(BinaryReader::vec(&stderr)),
+    };
+    let status = match (status.code(), status.signal()) {
+        (Some(code), _) => code as i128,
+        (None, Some(signal)) => 128 + signal as i128,
+        (None, None) => return error("Unknown exit status"),
+    };
+    context.output.send(Value::Struct(Struct::new(
+        vec![
+            ("status".to_string(), Value::Integer(status)),
+            (
+                "stdout".to_string(),
+                Value::BinaryStream(BinaryReader::vec(&stdout)),
+            ),
+            ("stderr".to_string(), stderr),
+            ("timed_out".to_string(), Value::Bool(timed_out)),
+        ],
+        None,
+    )))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn strings(values: &[&str]) -> Value {
+        Value::List(List::new(
+            ValueType::String,
+            values.iter().map(|s| Value::string(s)).collect(),
+        ))
+    }
+
+    #[test]
+    fn argument_expansion() {
+        let arguments = vec![
+            Argument::unnamed(Value::string("-rf")),
+            Argument::unnamed(strings(&["a", "b"])),
+            Argument::named("v", Value::Bool(true)),
+            Argument::named("quiet", Value::Bool(false)),
+            Argument::named("include", strings(&["x", "y"])),
+            Argument::named("depth", Value::Integer(3)),
+            Argument::named("n", Value::Integer(1)),
+            Argument::unnamed(Value::File(PathBuf::from("some/file"))),
+        ];
+        assert_eq!(
+            argv(arguments.clone(), &OptionStyle::Separate).unwrap(),
+            vec![
+                "-rf",
+                "a",
+                "b",
+                "-v",
+                "--include",
+                "x",
+                "--include",
+                "y",
+                "--depth",
+                "3",
+                "-n",
+                "1",
+                "some/file"
+            ]
+        );
+        assert_eq!(
+            argv(arguments, &OptionStyle::Equals).unwrap(),
+            vec![
+                "-rf",
+                "a",
+                "b",
+                "-v",
+                "--include=x",
+                "--include=y",
+                "--depth=3",
+                "-n",
+                "1",
+                "some/file"
+            ]
+        );
+    }
+}
@@ -1,15 +1,16 @@
-use crate::lang::errors::{argument_error, to_crush_error, CrushResult};
+use crate::lang::errors::{error, to_crush_error, CrushResult};
 use crate::lang::scope::Scope;
 use crate::lang::{
-    binary::BinaryReader, execution_context::ExecutionContext, list::List, value::Value,
-    value::ValueType,
+    execution_context::ExecutionContext, list::List, value::Value, value::ValueType,
 };
 use signature::signature;
 use std::env;
 
+mod cmd;
 mod r#for;
 mod r#if;
 mod r#loop;
+mod r#try;
 mod r#while;
 
 use crate::lang::argument::ArgumentHandler;
@@ -27,49 +28,6 @@ pub fn r#continue(context: ExecutionContext) -> CrushResult<()> {
     context.output.empty()
 }
 
-pub fn cmd(mut context: ExecutionContext) -> CrushResult<()> {
-    if context.arguments.is_empty() {
-        return argument_error("No command given");
-    }
-    match context.arguments.remove(0).value {
-        Value::File(f) => {
-            let mut cmd = std::process::Command::new(f.as_os_str());
-            for a in context.arguments.drain(..) {
-                match a.argument_type {
-                    None => {
-                        cmd.arg(a.value.to_string());
-                    }
-                    Some(name) => {
-                        if name.len() == 1 {
-                            cmd.arg(format!("-{}", name));
-                        } else {
-                            cmd.arg(format!("--{}", name));
-                        }
-                        match a.value {
-                            Value::Bool(true) => {}
-                            _ => {
-                                cmd.arg(a.value.to_string());
-                            }
-                        }
-                    }
-                }
-            }
-            let output = to_crush_error(cmd.output())?;
-            let errors = String::from_utf8_lossy(&output.stderr);
-            for e in errors.split('\n') {
-                let err = e.trim();
-                if !err.is_empty() {
-                    context.printer.error(err);
-                }
-            }
-            context
-                .output
-                .send(Value::BinaryStream(BinaryReader::vec(&output.stdout)))
-        }
-        _ => argument_error("Not a valid command"),
-    }
-}
-
 #[signature(
     sleep,
     can_block = true,
@@ -88,6 +46,25 @@ pub fn sleep(context: ExecutionContext) -> CrushResult<()> {
     Ok(())
 }
 
+#[signature(
+    raise,
+    short = "Raise an error.",
+    long = "If the argument is an error, e.g. the one passed to the catch clause of try, it is raised\n    again as is. Any other value is used as the message of a new error.",
+    example = "raise \"Could not connect to the database\""
+)]
+struct Raise {
+    #[description("the error, or the message of the error to raise.")]
+    error: Value,
+}
+
+pub fn raise(context: ExecutionContext) -> CrushResult<()> {
+    let cfg = Raise::parse(context.arguments, &context.printer)?;
+    match cfg.error {
+        Value::Error(err) => Err(err),
+        value => error(value.to_string()),
+    }
+}
+
 pub fn declare(root: &Scope) -> CrushResult<()> {
     let e = root.create_lazy_namespace(
         "control",
@@ -101,9 +78,11 @@ pub fn declare(root: &Scope) -> CrushResult<()> {
                 let _ = path.append(&mut dirs);
             }))?;
             env.declare("cmd_path", Value::List(path))?;
+            env.declare("cmd_option_style", Value::string("separate"))?;
             r#if::If::declare(env)?;
             r#while::While::declare(env)?;
             r#loop::Loop::declare(env)?;
+            r#try::Try::declare(env)?;
 
             env.declare_condition_command(
                 "for",
@@ -139,14 +118,39 @@ pub fn declare(root: &Scope) -> CrushResult<()> {
             )?;
             env.declare_command(
                 "cmd",
-                cmd,
+                cmd::cmd,
                 true,
                 "cmd external_command:(file|string) @arguments:any",
                 "Execute external commands",
-                None,
+                Some(
+                    r#"    Unnamed arguments that are lists are expanded into one argument per element,
+    globs are expanded into the files they match and all other values, including
+    strings starting with a hyphen, are passed on as exactly one argument.
+
+    Named arguments are turned into options, -x for single character names and
+    --name otherwise. A value of true turns the option on, false leaves it out and
+    a list repeats the option once per element. Depending on the variable
+    cmd_option_style, other values are passed as "--name value" (separate) or as
+    "--name=value" (equals).
+
+    Example:
+
+    cmd ls l=true (list:of %.rs %.toml)"#,
+                ),
                 Known(ValueType::BinaryStream),
             )?;
+            env.declare_command(
+                "which",
+                cmd::which,
+                false,
+                "which name:string",
+                "Find the external command with the specified name in cmd_path",
+                None,
+                Known(ValueType::File),
+            )?;
+            cmd::Run::declare(env)?;
             Sleep::declare(env)?;
+            Raise::declare(env)?;
             Ok(())
         }),
     )?;
@@ -0,0 +1,59 @@
+use crate::lang::argument::{Argument, ArgumentHandler};
+use crate::lang::command::Command;
+use crate::lang::errors::{CrushResult, Kind};
+use crate::lang::execution_context::ExecutionContext;
+use crate::lang::stream::channels;
+use crate::lang::value::Value;
+use signature::signature;
+
+#[signature(
+    r#try,
+    can_block = true,
+    short = "Execute a command, and handle the error if it fails.",
+    long = "If the body fails, its output is discarded and the catch clause is invoked with the error as\n    its only argument. Errors have the fields kind, message, command, line, column and cause.\n\n    Because try has to know if the body failed before passing on its output, any stream\n    produced by the body is read in full before it is passed on.\n\n    Lines that external commands write to stderr are printed as usual and do not make the body\n    fail. Neither does a non-zero exit status, because cmd ignores it. Use run to check the exit\n    status of an external command.",
+    example = "try {cd /nonexistent} catch={|err| echo err:message}"
+)]
+pub struct Try {
+    #[description("the command to execute.")]
+    body: Command,
+    #[description(
+        "the command to invoke if the body fails. If not specified, errors are ignored."
+    )]
+    catch: Option<Command>,
+}
+
+fn r#try(context: ExecutionContext) -> CrushResult<()> {
+    let cfg: Try = Try::parse(context.arguments.clone(), &context.printer)?;
+    let (printer, errors) = context.printer.capturing();
+    let (sender, receiver) = channels();
+
+    let mut failure = cfg
+        .body
+        .invoke(ExecutionContext {
+            input: context.input.clone(),
+            output: sender,
+            arguments: Vec::new(),
+            env: context.env.clone(),
+            this: None,
+            printer,
+        })
+        .err();
+    let value = receiver.recv().map(|v| v.materialize());
+
+    // The error channel is disconnected once every thread started by the body is done with it
+    for err in errors.iter() {
+        if failure.is_none() {
+            failure = Some(err);
+        }
+    }
+
+    match failure.filter(|err| err.kind != Kind::SendError) {
+        None => context.output.send(value.unwrap_or(Value::Empty())),
+        Some(err) => match cfg.catch {
+            Some(catch) => {
+                catch.invoke(context.with_args(vec![Argument::unnamed(Value::Error(err))], None))
+            }
+            None => context.output.send(Value::Empty()),
+        },
+    }
+}
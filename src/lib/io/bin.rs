@@ -1,4 +1,5 @@
 use crate::lang::argument::ArgumentHandler;
+use crate::lang::command::InputType;
 use crate::lang::errors::{argument_error, to_crush_error, CrushResult};
 use crate::lang::files::Files;
 use crate::lang::scope::ScopeLoader;
@@ -7,6 +8,7 @@ use signature::signature;
 
 #[signature(
     from,
+    input = InputType::Binary,
     can_block = true,
     short = "Read specified files (or input) as a binary stream"
 )]
@@ -1,24 +1,127 @@
 use crate::lang::execution_context::ExecutionContext;
-use crate::{
-    lang::errors::CrushError,
-    lang::{table::Row, value::Value},
-};
+use crate::lang::{table::Row, value::Value};
 use std::{io::prelude::*, io::BufReader};
 
-use crate::lang::errors::{error, to_crush_error, CrushResult};
+use crate::lang::errors::{argument_error, data_error, error, to_crush_error, CrushResult};
 use crate::lang::table::ColumnType;
 
 use crate::lang::argument::ArgumentHandler;
+use crate::lang::command::InputType;
 use crate::lang::files::Files;
 use crate::lang::ordered_string_map::OrderedStringMap;
 use crate::lang::scope::ScopeLoader;
 use crate::lang::value::ValueType;
 use signature::signature;
 
+/** The types a column can be inferred to be of, in the order they are tried. */
+const INFERRED_TYPES: [ValueType; 5] = [
+    ValueType::Integer,
+    ValueType::Float,
+    ValueType::Bool,
+    ValueType::Time,
+    ValueType::String,
+];
+
+/**
+  Splits its input into records of fields, as described by RFC 4180. Fields may be quoted, in
+  which case they can contain separators, newlines and quotes, the latter written as two quotes.
+  Records end with either a CRLF or a LF, and empty lines are skipped.
+*/
+struct Records<R: BufRead> {
+    reader: R,
+    separator: char,
+    quote: char,
+    line: usize,
+}
+
+impl<R: BufRead> Records<R> {
+    /** The next record and the number of the line it starts on, or None at the end of input. */
+    fn next(&mut self) -> CrushResult<Option<(usize, Vec<String>)>> {
+        let mut buf = String::new();
+        loop {
+            buf.clear();
+            if to_crush_error(self.reader.read_line(&mut buf))? == 0 {
+                return Ok(None);
+            }
+            self.line += 1;
+            if !buf.trim_end_matches(['\n', '\r']).is_empty() {
+                break;
+            }
+        }
+
+        let start = self.line;
+        let mut fields = Vec::new();
+        let mut field = String::new();
+        let mut quoted = false;
+        let mut in_quotes = false;
+        loop {
+            let mut chars = buf.chars().peekable();
+            while let Some(c) = chars.next() {
+                if in_quotes {
+                    if c != self.quote {
+                        field.push(c);
+                    } else if chars.peek() == Some(&self.quote) {
+                        chars.next();
+                        field.push(c);
+                    } else {
+                        in_quotes = false;
+                    }
+                } else if c == self.quote && field.is_empty() && !quoted {
+                    in_quotes = true;
+                    quoted = true;
+                } else if c == self.separator {
+                    fields.push(std::mem::take(&mut field));
+                    quoted = false;
+                } else if c == '\n' || (c == '\r' && chars.peek() == Some(&'\n')) {
+                    break;
+                } else {
+                    field.push(c);
+                }
+            }
+            if !in_quotes {
+                fields.push(field);
+                return Ok(Some((start, fields)));
+            }
+            buf.clear();
+            if to_crush_error(self.reader.read_line(&mut buf))? == 0 {
+                return data_error(
+                    format!(
+                        "csv: Quoted field starting on line {} is never closed",
+                        start
+                    )
+                    .as_str(),
+                );
+            }
+            self.line += 1;
+        }
+    }
+}
+
+/**
+  The first of the inferred types that every one of the specified values can be parsed as, or
+  string if there are no values to go by. Empty values are missing, and fit every type.
+*/
+fn infer(values: &[&str]) -> ValueType {
+    let values = values.iter().filter(|v| !v.is_empty()).collect::<Vec<_>>();
+    if values.is_empty() {
+        return ValueType::String;
+    }
+    INFERRED_TYPES
+        .iter()
+        .find(|t| values.iter().all(|v| t.parse(v).is_ok()))
+        .unwrap_or(&ValueType::String)
+        .clone()
+}
+
 #[signature(
     from,
+    input = InputType::Binary,
+    can_block = true,
     example = "csv:from separator=\",\" head=1 name=string age=integer nick=string",
-    short = "Parse specified files as CSV files"
+    short = "Parse specified files as CSV files",
+    long = "Fields may be quoted with double quotes, in which case they can contain separators, newlines and quotes, the latter written as two double quotes.",
+    long = "Column names and types can be specified as named arguments. Without them, the names are taken from the first row, and the type of each column is inferred from the first rows: integer, float, bool or time if all its non-empty fields can be parsed as such, otherwise string.",
+    long = "Empty fields in columns of other types than string are missing values, which are read as empty values. Columns that may hold them are of type any instead: inferred columns with an empty field among the first rows, and every column of another type than string when ragged is set. An empty field in any other column is an error."
 )]
 #[derive(Debug)]
 struct From {
@@ -30,79 +133,235 @@ struct From {
     #[named()]
     #[description("name and type of all columns.")]
     columns: OrderedStringMap<ValueType>,
-    #[description("column separator.")]
+    #[description("column separator, e.g. \"\\t\" for tab separated values.")]
     #[default(',')]
     separator: char,
+    #[description("the character fields are quoted with.")]
+    #[default('"')]
+    quote: char,
     #[default(0usize)]
-    #[description("skip this many lines of inpit from the beginning.")]
+    #[description("skip this many records of input from the beginning.")]
     head: usize,
     #[description("trim this character from start and end of every value.")]
     trim: Option<char>,
+    #[description(
+        "the first record holds the names of the columns, and is not part of the output. Defaults to true if no columns are specified."
+    )]
+    header: Option<bool>,
+    #[default(100usize)]
+    #[description("the number of records to infer the type of each column from.")]
+    sample: usize,
+    #[default(false)]
+    #[description(
+        "allow records with a different number of fields than there are columns. Missing fields are empty, extra fields are ignored."
+    )]
+    ragged: bool,
 }
 
 fn from(context: ExecutionContext) -> CrushResult<()> {
     let cfg: From = From::parse(context.arguments, &context.printer)?;
-    let columns = cfg
-        .columns
-        .iter()
-        .map(|(k, v)| ColumnType::new(k, v.clone()))
-        .collect::<Vec<_>>();
-    let output = context.output.initialize(columns.clone())?;
-
-    let mut reader = BufReader::new(cfg.files.reader(context.input)?);
-
-    let separator = cfg.separator;
-    let trim = cfg.trim;
-    let skip = cfg.head;
-
-    let mut line = String::new();
-    let mut skipped = 0usize;
-    loop {
-        line.clear();
-        to_crush_error(reader.read_line(&mut line))?;
-        if line.is_empty() {
+    let mut records = Records {
+        reader: BufReader::new(cfg.files.reader(context.input)?),
+        separator: cfg.separator,
+        quote: cfg.quote,
+        line: 0,
+    };
+    for _ in 0..cfg.head {
+        if records.next()?.is_none() {
             break;
         }
-        if skipped < skip {
-            skipped += 1;
-            continue;
-        }
-        let line_without_newline = &line[0..line.len() - 1];
-        let mut split: Vec<&str> = line_without_newline
-            .split(separator)
-            .map(|s| trim.map(|c| s.trim_matches(c)).unwrap_or(s))
-            .collect();
-
-        if split.len() != columns.len() {
-            return error("csv: Wrong number of columns in CSV file");
+    }
+
+    let header = if cfg.header.unwrap_or(cfg.columns.is_empty()) {
+        records.next()?.map(|(_, names)| names)
+    } else {
+        None
+    };
+    let width = match (&header, cfg.columns.is_empty()) {
+        (_, false) => cfg.columns.len(),
+        (Some(names), true) => names.len(),
+        (None, true) => {
+            return argument_error("csv: Specify the columns, or read their names from a header")
         }
+    };
 
+    let ragged = cfg.ragged;
+    let trim = cfg.trim;
+    let fields = |line: usize, mut record: Vec<String>| -> CrushResult<Vec<String>> {
+        if record.len() != width {
+            if !ragged {
+                return data_error(
+                    format!(
+                        "csv: Record on line {} has {} fields, expected {}",
+                        line,
+                        record.len(),
+                        width
+                    )
+                    .as_str(),
+                );
+            }
+            record.resize(width, String::new());
+        }
         if let Some(trim) = trim {
-            split = split.iter().map(|s| s.trim_matches(trim)).collect();
+            for field in record.iter_mut() {
+                *field = field.trim_matches(trim).to_string();
+            }
         }
+        Ok(record)
+    };
 
-        match split
+    let mut sample = Vec::new();
+    let columns = if cfg.columns.is_empty() {
+        while sample.len() < cfg.sample {
+            match records.next()? {
+                Some((line, record)) => sample.push((line, fields(line, record)?)),
+                None => break,
+            }
+        }
+        header
+            .unwrap_or_default()
+            .iter()
+            .enumerate()
+            .map(|(idx, name)| {
+                let values = sample
+                    .iter()
+                    .map(|(_, record)| record[idx].as_str())
+                    .collect::<Vec<_>>();
+                ColumnType::new(name, infer(&values))
+            })
+            .collect::<Vec<_>>()
+    } else {
+        cfg.columns
             .iter()
-            .zip(columns.iter())
-            .map(|(s, t)| t.cell_type.parse(*s))
-            .collect::<Result<Vec<Value>, CrushError>>()
-        {
-            Ok(cells) => {
-                let _ = output.send(Row::new(cells));
+            .map(|(k, v)| ColumnType::new(k, v.clone()))
+            .collect::<Vec<_>>()
+    };
+    // The type that the fields of each column are parsed as, which differs from the type of the
+    // column when the column may hold missing values
+    let parsers = columns
+        .iter()
+        .map(|c| c.cell_type.clone())
+        .collect::<Vec<_>>();
+    let columns = columns
+        .into_iter()
+        .enumerate()
+        .map(|(idx, column)| {
+            let missing = ragged || sample.iter().any(|(_, record)| record[idx].is_empty());
+            if missing && column.cell_type != ValueType::String {
+                ColumnType::new(&column.name, ValueType::Any)
+            } else {
+                column
             }
-            Err(err) => {
-                return Err(err);
+        })
+        .collect::<Vec<_>>();
+    let output = context.output.initialize(columns.clone())?;
+
+    let parse = |line: usize, record: Vec<String>| -> CrushResult<Row> {
+        let mut cells = Vec::with_capacity(width);
+        for ((field, column), parser) in record.iter().zip(columns.iter()).zip(parsers.iter()) {
+            if field.is_empty() && column.cell_type == ValueType::Any {
+                cells.push(Value::Empty());
+                continue;
+            }
+            match parser.parse(field) {
+                Ok(cell) => cells.push(cell),
+                Err(_) => {
+                    return data_error(
+                        format!(
+                            "csv: Field \"{}\" on line {} is not a valid {} for column {}",
+                            field,
+                            line,
+                            parser.to_string(),
+                            column.name
+                        )
+                        .as_str(),
+                    )
+                }
             }
         }
+        Ok(Row::new(cells))
+    };
+
+    for (line, record) in sample {
+        output.send(parse(line, record)?)?;
+    }
+    while let Some((line, record)) = records.next()? {
+        output.send(parse(line, fields(line, record)?)?)?;
     }
     Ok(())
 }
 
+/** Write a field, quoting it if it would otherwise not be read back as the same field. */
+fn write_field(out: &mut dyn Write, field: &str, separator: char) -> CrushResult<()> {
+    if field.contains([separator, '"', '\n', '\r']) {
+        to_crush_error(write!(out, "\"{}\"", field.replace('"', "\"\"")))
+    } else {
+        to_crush_error(out.write_all(field.as_bytes()))
+    }
+}
+
+/** Format a cell so that csv:from parses it back as the same value. */
+fn format_cell(cell: &Value) -> String {
+    match cell {
+        Value::Float(f) => format!("{:?}", f),
+        Value::Time(t) => t.to_rfc3339(),
+        Value::Empty() => String::new(),
+        c => c.to_string(),
+    }
+}
+
+#[signature(
+    to,
+    can_block = true,
+    short = "Serialize a table or a table stream to CSV format",
+    long = "The first record holds the names of the columns. Fields containing separators, quotes or newlines are quoted. If no file is specified, output is returned as a BinaryStream.",
+    example = "ps | csv:to processes.csv"
+)]
+struct To {
+    #[unnamed()]
+    #[description("the file to write to. If unspecified, the output is a binary_stream.")]
+    file: Files,
+    #[description("column separator, e.g. \"\\t\" for tab separated values.")]
+    #[default(',')]
+    separator: char,
+    #[default(true)]
+    #[description("write the names of the columns as the first record.")]
+    header: bool,
+}
+
+fn to(context: ExecutionContext) -> CrushResult<()> {
+    let cfg: To = To::parse(context.arguments, &context.printer)?;
+    match context.input.recv()?.stream() {
+        Some(mut input) => {
+            let separator = cfg.separator;
+            let mut out = cfg.file.writer(context.output)?;
+            let mut write_record = |fields: Vec<String>| -> CrushResult<()> {
+                for (idx, field) in fields.iter().enumerate() {
+                    if idx > 0 {
+                        to_crush_error(write!(out, "{}", separator))?;
+                    }
+                    write_field(out.as_mut(), field, separator)?;
+                }
+                to_crush_error(out.write_all(b"\r\n"))
+            };
+            if cfg.header {
+                write_record(input.types().iter().map(|c| c.name.clone()).collect())?;
+            }
+            while let Ok(row) = input.read() {
+                write_record(row.cells().iter().map(format_cell).collect())?;
+            }
+            Ok(())
+        }
+        None => error("Expected a table or a table stream"),
+    }
+}
+
 pub fn declare(root: &mut ScopeLoader) -> CrushResult<()> {
     root.create_lazy_namespace(
         "csv",
         Box::new(move |env| {
             From::declare(env)?;
+            To::declare(env)?;
             Ok(())
         }),
     )?;
@@ -0,0 +1,143 @@
+use crate::lang::argument::ArgumentHandler;
+use crate::lang::command::OutputType::Known;
+use crate::lang::command::{Command, InputType};
+use crate::lang::dict::Dict;
+use crate::lang::errors::{argument_error, mandate, to_crush_error, CrushResult};
+use crate::lang::execution_context::{ExecutionContext, This};
+use crate::lang::files::Files;
+use crate::lang::r#struct::Struct;
+use crate::lang::scope::ScopeLoader;
+use crate::lang::table::{ColumnType, Row, Table};
+use crate::lang::value::{Value, ValueType};
+use lazy_static::lazy_static;
+use ordered_map::OrderedMap;
+use scraper::{ElementRef, Html, Selector};
+use signature::signature;
+use std::io::{BufReader, Read};
+
+lazy_static! {
+    static ref SELECT_OUTPUT_TYPE: Vec<ColumnType> = vec![
+        ColumnType::new("tag", ValueType::String),
+        ColumnType::new("text", ValueType::String),
+        ColumnType::new(
+            "attributes",
+            ValueType::Dict(Box::from(ValueType::String), Box::from(ValueType::String))
+        ),
+        ColumnType::new("html", ValueType::String),
+    ];
+    static ref METHODS: OrderedMap<String, Command> = {
+        let mut res: OrderedMap<String, Command> = OrderedMap::new();
+        let path = vec!["global", "io", "html", "document"];
+        let _ = Select::declare_method(&mut res, &path);
+        res
+    };
+}
+
+/** A struct holding the source of a document, with the methods for querying it as its parent. */
+fn document(source: String) -> Struct {
+    let methods = Struct::new(
+        METHODS
+            .iter()
+            .map(|(name, command)| (name.clone(), Value::Command(command.copy())))
+            .collect(),
+        None,
+    );
+    Struct::new(
+        vec![("html".to_string(), Value::string(&source))],
+        Some(methods),
+    )
+}
+
+#[signature(
+    from,
+    can_block = true,
+    output = Known(ValueType::Struct),
+    input = InputType::Binary,
+    short = "Parse an HTML document",
+    long = "The output is a document struct. Its html field holds the source of the document, and its select method finds the elements matching a CSS selector.",
+    example = "(http \"https://example.com\"):body | html:from"
+)]
+struct From {
+    #[unnamed()]
+    #[description(
+        "source. If unspecified, will read from io, which must be a binary or binary_stream."
+    )]
+    files: Files,
+}
+
+fn from(context: ExecutionContext) -> CrushResult<()> {
+    let cfg: From = From::parse(context.arguments, &context.printer)?;
+    let mut reader = BufReader::new(cfg.files.reader(context.input)?);
+    let mut source = String::new();
+    to_crush_error(reader.read_to_string(&mut source))?;
+    context.output.send(Value::Struct(document(source)))
+}
+
+/** The text of an element and its descendants, with runs of whitespace collapsed to one space. */
+fn text(element: &ElementRef) -> String {
+    element
+        .text()
+        .flat_map(|t| t.split_whitespace())
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+fn attributes(element: &ElementRef) -> CrushResult<Dict> {
+    let res = Dict::new(ValueType::String, ValueType::String);
+    for (name, value) in element.value().attrs() {
+        res.insert(Value::string(name), Value::string(value))?;
+    }
+    Ok(res)
+}
+
+#[signature(
+    select,
+    can_block = false,
+    output = Known(ValueType::Table(SELECT_OUTPUT_TYPE.clone())),
+    short = "The elements of the document matching a CSS selector",
+    long = "The output is a table with one row per matching element, in document order. The tag column holds the name of the element, the text column all text inside it with whitespace collapsed, the attributes column a dict of its attributes and the html column its source.",
+    example = "doc := (html:from index.html); doc:select \"a[href]\" | select ^text ^attributes"
+)]
+struct Select {
+    #[description("the CSS selector to match elements against.")]
+    selector: String,
+}
+
+fn select(context: ExecutionContext) -> CrushResult<()> {
+    let this = context.this.r#struct()?;
+    let cfg: Select = Select::parse(context.arguments, &context.printer)?;
+    let selector = match Selector::parse(&cfg.selector) {
+        Ok(selector) => selector,
+        Err(_) => return argument_error(format!("Invalid CSS selector {}", cfg.selector).as_str()),
+    };
+    let source = match mandate(this.get("html"), "Expected a document")? {
+        Value::String(s) => s,
+        _ => return argument_error("Expected the html field of a document to be a string"),
+    };
+    let html = Html::parse_document(&source);
+    let rows = html
+        .select(&selector)
+        .map(|element| {
+            Ok(Row::new(vec![
+                Value::string(element.value().name()),
+                Value::string(&text(&element)),
+                Value::Dict(attributes(&element)?),
+                Value::string(&element.html()),
+            ]))
+        })
+        .collect::<CrushResult<Vec<_>>>()?;
+    context
+        .output
+        .send(Value::Table(Table::new(SELECT_OUTPUT_TYPE.clone(), rows)))
+}
+
+pub fn declare(root: &mut ScopeLoader) -> CrushResult<()> {
+    root.create_lazy_namespace(
+        "html",
+        Box::new(move |env| {
+            From::declare(env)?;
+            Ok(())
+        }),
+    )?;
+    Ok(())
+}
@@ -6,6 +6,7 @@ use crate::{
 use std::io::{BufReader, Write};
 
 use crate::lang::argument::ArgumentHandler;
+use crate::lang::command::InputType;
 use crate::lang::command::OutputType::Unknown;
 use crate::lang::errors::Kind::InvalidData;
 use crate::lang::errors::{error, mandate, to_crush_error, CrushResult};
@@ -17,7 +18,7 @@ use signature::signature;
 use std::collections::HashSet;
 use std::convert::TryFrom;
 
-fn from_json(json_value: &serde_json::Value) -> CrushResult<Value> {
+pub fn from_json(json_value: &serde_json::Value) -> CrushResult<Value> {
     match json_value {
         serde_json::Value::Null => Ok(Value::Empty()),
         serde_json::Value::Bool(b) => Ok(Value::Bool(*b)),
@@ -26,11 +27,17 @@ fn from_json(json_value: &serde_json::Value) -> CrushResult<Value> {
                 Ok(Value::Integer(f.as_u64().expect("") as i128))
             } else if f.is_i64() {
                 Ok(Value::Integer(f.as_i64().expect("") as i128))
+            } else if f
+                .to_string()
+                .trim_start_matches('-')
+                .bytes()
+                .all(|b| b.is_ascii_digit())
+            {
+                // Numbers are kept as text, so integers of any size can be read exactly
+                Value::parse_integer(&f.to_string())
             } else {
-                Ok(Value::Float(f.as_f64().ok_or(CrushError {
-                    kind: InvalidData,
-                    message: "Not a valid number".to_string(),
-                })?))
+                Ok(Value::Float(f.as_f64()
+                    .ok_or(CrushError::new(InvalidData, "Not a valid number"))?))
             }
         }
         serde_json::Value::String(s) => Ok(Value::string(s.as_str())),
@@ -85,8 +92,10 @@ fn from_json(json_value: &serde_json::Value) -> CrushResult<Value> {
     }
 }
 
-fn to_json(value: Value) -> CrushResult<serde_json::Value> {
+pub fn to_json(value: Value) -> CrushResult<serde_json::Value> {
     match value.materialize() {
+        Value::Empty() => Ok(serde_json::Value::Null),
+
         Value::File(s) => Ok(serde_json::Value::from(mandate(
             s.to_str(),
             "Invalid filename",
@@ -94,7 +103,12 @@ fn to_json(value: Value) -> CrushResult<serde_json::Value> {
 
         Value::String(s) => Ok(serde_json::Value::from(s)),
 
-        Value::Integer(i) => Ok(serde_json::Value::from(to_crush_error(i64::try_from(i))?)),
+        Value::Integer(i) => match i64::try_from(i) {
+            Ok(i) => Ok(serde_json::Value::from(i)),
+            Err(_) => to_crush_error(serde_json::from_str(&i.to_string())),
+        },
+
+        Value::BigInteger(i) => to_crush_error(serde_json::from_str(&i.to_string())),
 
         Value::List(l) => Ok(serde_json::Value::Array(
             l.dump()
@@ -144,16 +158,31 @@ fn to_json(value: Value) -> CrushResult<serde_json::Value> {
 from,
 can_block = true,
 output = Unknown,
+input = InputType::Binary,
 short = "Parse json format",
+long = "With stream=true, the input is read as a sequence of json documents, and each document is output as a row with a single value column as soon as it is complete.",
 example = "(http \"https://jsonplaceholder.typicode.com/todos/3\"):body | json:from")]
 struct From {
     #[unnamed()]
     files: Files,
+    #[description("read a sequence of json documents into a table stream.")]
+    #[default(false)]
+    stream: bool,
 }
 
 pub fn from(context: ExecutionContext) -> CrushResult<()> {
     let cfg: From = From::parse(context.arguments, &context.printer)?;
     let reader = BufReader::new(cfg.files.reader(context.input)?);
+    if cfg.stream {
+        let output = context
+            .output
+            .initialize(vec![ColumnType::new("value", ValueType::Any)])?;
+        for document in serde_json::Deserializer::from_reader(reader).into_iter() {
+            let serde_value = to_crush_error(document)?;
+            output.send(Row::new(vec![from_json(&serde_value)?]))?;
+        }
+        return Ok(());
+    }
     let serde_value = to_crush_error(serde_json::from_reader(reader))?;
     let crush_value = from_json(&serde_value)?;
     context.output.send(crush_value)
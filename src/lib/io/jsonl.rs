@@ -0,0 +1,246 @@
+use crate::lang::argument::ArgumentHandler;
+use crate::lang::command::InputType;
+use crate::lang::errors::{data_error, error, to_crush_error, CrushResult};
+use crate::lang::execution_context::ExecutionContext;
+use crate::lang::files::Files;
+use crate::lang::r#struct::Struct;
+use crate::lang::scope::ScopeLoader;
+use crate::lang::table::{ColumnType, Row};
+use crate::lang::value::{Value, ValueType};
+use signature::signature;
+use std::io::{BufRead, BufReader, Write};
+
+use super::json::{from_json, to_json};
+
+/**
+  The columns of a stream holding the specified objects. A column is of type any if its values
+  are of different types, or if it is null or missing in some of the objects, because it may then
+  be missing from later lines too.
+*/
+fn infer(sample: &[Struct]) -> Vec<ColumnType> {
+    let mut columns: Vec<ColumnType> = Vec::new();
+    for object in sample {
+        for (name, value) in object.local_elements() {
+            let cell_type = match value {
+                Value::Empty() => ValueType::Any,
+                value => value.value_type(),
+            };
+            match columns.iter_mut().find(|c| c.name == name) {
+                Some(column) => {
+                    if column.cell_type != cell_type {
+                        column.cell_type = ValueType::Any;
+                    }
+                }
+                None => columns.push(ColumnType::new(&name, cell_type)),
+            }
+        }
+    }
+    for column in columns.iter_mut() {
+        if sample
+            .iter()
+            .any(|object| object.get(&column.name).is_none())
+        {
+            column.cell_type = ValueType::Any;
+        }
+    }
+    columns
+}
+
+/** The name of the column holding what doesn't fit the other columns. */
+fn rest_name(columns: &[ColumnType]) -> String {
+    let mut name = "rest".to_string();
+    while columns.iter().any(|c| c.name == name) {
+        name.insert(0, '_');
+    }
+    name
+}
+
+/**
+  A row with the fields of a document that fit the columns, followed by a struct of the fields
+  that don't, or empty if all of them do. A document that isn't an object doesn't fit at all,
+  and is put in the value field of that struct. Columns of type any are empty if the document
+  has no value for them, other columns are an error.
+*/
+fn to_row(line: usize, document: Value, columns: &[ColumnType]) -> CrushResult<Row> {
+    let (fields, mut rest) = match document {
+        Value::Struct(object) => (object.local_elements(), Vec::new()),
+        document => (Vec::new(), vec![("value".to_string(), document)]),
+    };
+    let mut cells = vec![None; columns.len()];
+    for (name, value) in fields {
+        match columns.iter().position(|c| c.name == name) {
+            Some(idx) if columns[idx].cell_type.is(&value) => cells[idx] = Some(value),
+            _ => rest.push((name, value)),
+        }
+    }
+    let mut cells = cells
+        .into_iter()
+        .zip(columns)
+        .map(|(cell, column)| match cell {
+            Some(cell) => Ok(cell),
+            None if column.cell_type == ValueType::Any => Ok(Value::Empty()),
+            None => data_error(
+                format!(
+                    "jsonl: Line {} has no {} value for column {}, try a larger sample",
+                    line,
+                    column.cell_type.to_string(),
+                    column.name
+                )
+                .as_str(),
+            ),
+        })
+        .collect::<CrushResult<Vec<_>>>()?;
+    cells.push(if rest.is_empty() {
+        Value::Empty()
+    } else {
+        Value::Struct(Struct::new(rest, None))
+    });
+    Ok(Row::new(cells))
+}
+
+/** Reads one JSON document per line, skipping empty lines. */
+struct Documents<R: BufRead> {
+    reader: R,
+    line: usize,
+}
+
+impl<R: BufRead> Documents<R> {
+    fn next(&mut self) -> CrushResult<Option<(usize, Value)>> {
+        let mut buf = String::new();
+        loop {
+            buf.clear();
+            if to_crush_error(self.reader.read_line(&mut buf))? == 0 {
+                return Ok(None);
+            }
+            self.line += 1;
+            if !buf.trim().is_empty() {
+                break;
+            }
+        }
+        match serde_json::from_str(&buf) {
+            Ok(document) => Ok(Some((self.line, from_json(&document)?))),
+            Err(e) => {
+                data_error(format!("jsonl: Invalid JSON on line {}: {}", self.line, e).as_str())
+            }
+        }
+    }
+}
+
+#[signature(
+    from,
+    can_block = true,
+    input = InputType::Binary,
+    short = "Parse JSON lines, i.e. one JSON document per line, into a table stream",
+    long = "The input is read one line at a time, so it can be arbitrarily large. If the first lines all hold objects, the columns are inferred from their fields. A column is of type any if its values are of different types, or if it is null or missing in some of them. Such columns are empty in later lines that have no value for them, while a later line without a value of the right type for one of the other columns is an error.",
+    long = "The last column, named rest, holds a struct of the fields of later lines that don't fit the inferred columns, because they are unknown or of another type, or is empty if all fields fit. A later line that isn't an object is put in the value field of that struct.",
+    long = "Otherwise, or with infer=false, the output has a single column named value, holding every document as is.",
+    example = "jsonl:from access.log | where {status >= 500}"
+)]
+struct From {
+    #[unnamed()]
+    #[description(
+        "source. If unspecified, will read from io, which must be a binary or binary_stream."
+    )]
+    files: Files,
+    #[default(100usize)]
+    #[description("the number of lines to infer the columns from.")]
+    sample: usize,
+    #[default(true)]
+    #[description("infer columns from the fields of the objects.")]
+    infer: bool,
+}
+
+fn from(context: ExecutionContext) -> CrushResult<()> {
+    let cfg: From = From::parse(context.arguments, &context.printer)?;
+    let mut documents = Documents {
+        reader: BufReader::new(cfg.files.reader(context.input)?),
+        line: 0,
+    };
+
+    let mut sample = Vec::new();
+    while sample.len() < cfg.sample {
+        match documents.next()? {
+            Some(document) => sample.push(document),
+            None => break,
+        }
+    }
+
+    let objects = sample
+        .iter()
+        .filter_map(|(_, d)| match d {
+            Value::Struct(s) => Some(s.clone()),
+            _ => None,
+        })
+        .collect::<Vec<_>>();
+    if !cfg.infer || objects.is_empty() || objects.len() != sample.len() {
+        let output = context
+            .output
+            .initialize(vec![ColumnType::new("value", ValueType::Any)])?;
+        for (_, document) in sample {
+            output.send(Row::new(vec![document]))?;
+        }
+        while let Some((_, document)) = documents.next()? {
+            output.send(Row::new(vec![document]))?;
+        }
+        return Ok(());
+    }
+
+    let columns = infer(&objects);
+    let mut output_types = columns.clone();
+    output_types.push(ColumnType::new(&rest_name(&columns), ValueType::Any));
+    let output = context.output.initialize(output_types)?;
+    for (line, document) in sample {
+        output.send(to_row(line, document, &columns)?)?;
+    }
+    while let Some((line, document)) = documents.next()? {
+        output.send(to_row(line, document, &columns)?)?;
+    }
+    Ok(())
+}
+
+#[signature(
+    to,
+    can_block = true,
+    input = InputType::Table,
+    short = "Serialize every row of a table or a table stream as one line of JSON",
+    long = "Rows are written one at a time as they are read. Every row is written as an object, except rows with a single column holding a struct, like the output of jsonl:from infer=false, which are written as that struct. If no file is specified, output is returned as a BinaryStream.",
+    example = "ps | jsonl:to processes.jsonl"
+)]
+struct To {
+    #[unnamed()]
+    #[description("the file to write to. If unspecified, the output is a binary_stream.")]
+    file: Files,
+}
+
+fn to(context: ExecutionContext) -> CrushResult<()> {
+    let cfg: To = To::parse(context.arguments, &context.printer)?;
+    match context.input.recv()?.stream() {
+        Some(mut input) => {
+            let mut out = cfg.file.writer(context.output)?;
+            let types = input.types().to_vec();
+            while let Ok(row) = input.read() {
+                let value = match row.cells().as_slice() {
+                    [Value::Struct(_)] => row.into_vec().remove(0),
+                    _ => Value::Struct(row.into_struct(&types)),
+                };
+                let mut line = to_crush_error(serde_json::to_string(&to_json(value)?))?;
+                line.push('\n');
+                to_crush_error(out.write_all(line.as_bytes()))?;
+            }
+            Ok(())
+        }
+        None => error("Expected a table or a table stream"),
+    }
+}
+
+pub fn declare(root: &mut ScopeLoader) -> CrushResult<()> {
+    root.create_lazy_namespace(
+        "jsonl",
+        Box::new(move |env| {
+            From::declare(env)?;
+            To::declare(env)?;
+            Ok(())
+        }),
+    )?;
+    Ok(())
+}
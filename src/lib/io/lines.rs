@@ -1,4 +1,5 @@
 use crate::lang::argument::ArgumentHandler;
+use crate::lang::command::InputType;
 use crate::lang::errors::{argument_error, data_error, to_crush_error, CrushResult};
 use crate::lang::files::Files;
 use crate::lang::scope::ScopeLoader;
@@ -11,6 +12,7 @@ use std::io::{BufRead, BufReader};
 
 #[signature(
     from,
+    input = InputType::Binary,
     can_block = true,
     short = "Read specified files (or input) as a table with one line of text per row"
 )]
@@ -42,11 +44,9 @@ pub fn from(context: ExecutionContext) -> CrushResult<()> {
             s = &s[1..];
         }
         while s.ends_with('\r') {
-            s = &s[0..line.len() - 1];
+            s = &s[0..s.len() - 1];
         }
-        context
-            .printer
-            .handle_error(output.send(Row::new(vec![Value::string(s)])));
+        output.send(Row::new(vec![Value::string(s)]))?;
         line.clear();
     }
     Ok(())
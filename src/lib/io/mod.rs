@@ -12,13 +12,17 @@ use signature::signature;
 
 mod bin;
 mod csv;
+mod html;
 mod http;
 mod json;
+mod jsonl;
 mod lines;
 mod pup;
 mod split;
 mod toml;
 mod words;
+mod xml;
+mod yaml;
 
 pub fn val(mut context: ExecutionContext) -> CrushResult<()> {
     context.arguments.check_len(1)?;
@@ -86,12 +90,16 @@ pub fn declare(root: &Scope) -> CrushResult<()> {
         Box::new(move |env| {
             bin::declare(env)?;
             csv::declare(env)?;
+            html::declare(env)?;
             pup::declare(env)?;
             toml::declare(env)?;
             json::declare(env)?;
+            jsonl::declare(env)?;
             lines::declare(env)?;
             split::declare(env)?;
             words::declare(env)?;
+            xml::declare(env)?;
+            yaml::declare(env)?;
 
             http::Http::declare(env)?;
             Echo::declare(env)?;
@@ -1,4 +1,5 @@
 use crate::lang::argument::ArgumentHandler;
+use crate::lang::command::InputType;
 use crate::lang::command::OutputType::Unknown;
 use crate::lang::errors::CrushResult;
 use crate::lang::execution_context::ExecutionContext;
@@ -32,6 +33,7 @@ fn to(context: ExecutionContext) -> CrushResult<()> {
 from,
 can_block = true,
 output = Unknown,
+input = InputType::Binary,
 short = "Parse pup format",
 example = "pup:from serialized.pup")]
 struct From {
@@ -6,6 +6,7 @@ use crate::{
 use std::io::{BufReader, Read, Write};
 
 use crate::lang::argument::ArgumentHandler;
+use crate::lang::command::InputType;
 use crate::lang::command::OutputType::Unknown;
 use crate::lang::errors::{error, mandate, to_crush_error, CrushResult};
 use crate::lang::files::Files;
@@ -82,6 +83,7 @@ fn from_toml(toml_value: &toml::Value) -> CrushResult<Value> {
 from,
 can_block = true,
 output = Unknown,
+input = InputType::Binary,
 short = "Parse toml format",
 long = "Input can either be a binary stream or a file. All Toml types except\n    datetime are supported. Datetime is not supported because the rust toml\n    currently doesn't support accessing the internal state of a datetime.",
 example = "toml:from Cargo.toml")]
@@ -103,13 +105,22 @@ fn from(context: ExecutionContext) -> CrushResult<()> {
     Ok(())
 }
 
+fn too_large<T>(integer: &str) -> CrushResult<T> {
+    error(format!("The integer {} is too large for toml", integer))
+}
+
 fn to_toml(value: Value) -> CrushResult<toml::Value> {
     match value.materialize() {
         Value::File(s) => Ok(toml::Value::from(mandate(s.to_str(), "Invalid filename")?)),
 
         Value::String(s) => Ok(toml::Value::from(s.as_ref())),
 
-        Value::Integer(i) => Ok(toml::Value::from(to_crush_error(i64::try_from(i))?)),
+        Value::Integer(i) => match i64::try_from(i) {
+            Ok(i) => Ok(toml::Value::from(i)),
+            Err(_) => too_large(&i.to_string()),
+        },
+
+        Value::BigInteger(i) => too_large(&i.to_string()),
 
         Value::List(l) => Ok(toml::Value::Array(
             l.dump()
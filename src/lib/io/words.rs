@@ -1,4 +1,5 @@
 use crate::lang::argument::ArgumentHandler;
+use crate::lang::command::InputType;
 use crate::lang::errors::{to_crush_error, CrushResult};
 use crate::lang::files::Files;
 use crate::lang::scope::ScopeLoader;
@@ -12,6 +13,7 @@ use std::io::{BufRead, BufReader};
 
 #[signature(
     from,
+    input = InputType::Binary,
     can_block = true,
     short = "Read specified files (or input) as a table, split on word boundaries, and trim away punctuation."
 )]
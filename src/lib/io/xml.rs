@@ -0,0 +1,384 @@
+use crate::lang::argument::ArgumentHandler;
+use crate::lang::command::InputType;
+use crate::lang::command::OutputType::Unknown;
+use crate::lang::dict::Dict;
+use crate::lang::errors::{argument_error, data_error, mandate, to_crush_error, CrushResult};
+use crate::lang::execution_context::ExecutionContext;
+use crate::lang::files::Files;
+use crate::lang::list::List;
+use crate::lang::r#struct::Struct;
+use crate::lang::scope::ScopeLoader;
+use crate::lang::table::{ColumnType, Row};
+use crate::lang::value::{Value, ValueType};
+use lazy_static::lazy_static;
+use roxmltree::{Document, Node};
+use signature::signature;
+use std::io::{BufReader, Read, Write};
+
+lazy_static! {
+    static ref ELEMENT_TYPE: Vec<ColumnType> = vec![
+        ColumnType::new("name", ValueType::String),
+        ColumnType::new(
+            "attributes",
+            ValueType::Dict(Box::from(ValueType::String), Box::from(ValueType::String))
+        ),
+        ColumnType::new("children", ValueType::List(Box::from(ValueType::Struct))),
+        ColumnType::new("text", ValueType::String),
+    ];
+}
+
+/**
+  The fields of an element, in the order of ELEMENT_TYPE. The text is that of the text nodes
+  directly inside the element, with leading and trailing whitespace removed.
+*/
+fn element(node: Node) -> CrushResult<Vec<Value>> {
+    let attributes = Dict::new(ValueType::String, ValueType::String);
+    for attribute in node.attributes() {
+        attributes.insert(
+            Value::string(attribute.name()),
+            Value::string(attribute.value()),
+        )?;
+    }
+    let children = node
+        .children()
+        .filter(|n| n.is_element())
+        .map(|n| Ok(Value::Struct(element_struct(n)?)))
+        .collect::<CrushResult<Vec<_>>>()?;
+    let text = node
+        .children()
+        .filter_map(|n| if n.is_text() { n.text() } else { None })
+        .collect::<String>();
+    Ok(vec![
+        Value::string(node.tag_name().name()),
+        Value::Dict(attributes),
+        Value::List(List::new(ValueType::Struct, children)),
+        Value::string(text.trim()),
+    ])
+}
+
+fn element_struct(node: Node) -> CrushResult<Struct> {
+    Ok(Row::new(element(node)?).into_struct(&ELEMENT_TYPE))
+}
+
+/** One step of a path, e.g. the `item[@id]` in `//channel/item[@id]`. */
+struct Step {
+    descendants: bool,
+    name: Option<String>,
+    attribute: Option<(String, Option<String>)>,
+}
+
+impl Step {
+    fn matches(&self, node: &Node) -> bool {
+        node.is_element()
+            && self
+                .name
+                .as_ref()
+                .map(|n| n == node.tag_name().name())
+                .unwrap_or(true)
+            && match &self.attribute {
+                None => true,
+                Some((name, None)) => node.has_attribute(name.as_str()),
+                Some((name, Some(value))) => node.attribute(name.as_str()) == Some(value.as_str()),
+            }
+    }
+}
+
+fn invalid_path<T>(path: &str) -> CrushResult<T> {
+    argument_error(format!("Invalid path {}", path).as_str())
+}
+
+/** Parse the predicate of a step, i.e. `@name`, `@name='value'` or `@name="value"`. */
+fn parse_predicate(path: &str, predicate: &str) -> CrushResult<(String, Option<String>)> {
+    let predicate = match predicate.strip_prefix('@') {
+        Some(p) => p,
+        None => return invalid_path(path),
+    };
+    match predicate.find('=') {
+        None => Ok((predicate.to_string(), None)),
+        Some(idx) => {
+            let value = &predicate[idx + 1..];
+            let unquoted = value
+                .strip_prefix('\'')
+                .and_then(|v| v.strip_suffix('\''))
+                .or_else(|| value.strip_prefix('"').and_then(|v| v.strip_suffix('"')));
+            match unquoted {
+                Some(v) => Ok((predicate[..idx].to_string(), Some(v.to_string()))),
+                None => invalid_path(path),
+            }
+        }
+    }
+}
+
+fn parse_path(path: &str) -> CrushResult<Vec<Step>> {
+    let mut steps = Vec::new();
+    let mut remaining = path;
+    while !remaining.is_empty() {
+        let descendants = remaining.starts_with("//");
+        remaining = remaining.trim_start_matches('/');
+        let end = remaining.find('/').unwrap_or(remaining.len());
+        let step = &remaining[..end];
+        remaining = &remaining[end..];
+
+        let (test, attribute) = match step.find('[') {
+            Some(idx) => match step[idx + 1..].strip_suffix(']') {
+                Some(predicate) => (&step[..idx], Some(parse_predicate(path, predicate)?)),
+                None => return invalid_path(path),
+            },
+            None => (step, None),
+        };
+        if test.is_empty() {
+            return invalid_path(path);
+        }
+        steps.push(Step {
+            descendants,
+            name: if test == "*" {
+                None
+            } else {
+                Some(test.to_string())
+            },
+            attribute,
+        });
+    }
+    if steps.is_empty() {
+        return invalid_path(path);
+    }
+    Ok(steps)
+}
+
+/** The elements matching the path, in document order. */
+fn select<'a, 'input>(document: &'a Document<'input>, steps: &[Step]) -> Vec<Node<'a, 'input>> {
+    let mut nodes = vec![document.root()];
+    for step in steps {
+        let mut next = Vec::new();
+        for node in nodes {
+            let candidates: Vec<Node> = if step.descendants {
+                node.descendants().skip(1).collect()
+            } else {
+                node.children().collect()
+            };
+            for candidate in candidates {
+                if step.matches(&candidate) {
+                    next.push(candidate);
+                }
+            }
+        }
+        // Node ids are in document order
+        next.sort_by_key(|n| n.id().get());
+        next.dedup_by_key(|n| n.id().get());
+        nodes = next;
+    }
+    nodes
+}
+
+#[signature(
+    from,
+    can_block = true,
+    output = Unknown,
+    input = InputType::Binary,
+    short = "Parse xml format",
+    long = "Every element becomes a struct with the fields name, attributes, a dict of its attributes, children, a list of its child elements, and text, the text directly inside it. Names do not include namespace prefixes. Comments and processing instructions are ignored.",
+    long = "Without a path, the output is the root element. With a path, the output is a table stream with one row per matching element, and the same columns as the fields of an element. Paths are a subset of XPath: steps are separated by /, // matches elements at any depth, * matches any element, and a step can be followed by a predicate on an attribute, like [@id] or [@id='main'].",
+    example = "xml:from feed.xml path=\"//item/title\" | select ^text"
+)]
+struct From {
+    #[unnamed()]
+    #[description(
+        "source. If unspecified, will read from io, which must be a binary or binary_stream."
+    )]
+    files: Files,
+    #[description("output the elements matching this path as a table stream.")]
+    path: Option<String>,
+}
+
+fn from(context: ExecutionContext) -> CrushResult<()> {
+    let cfg: From = From::parse(context.arguments, &context.printer)?;
+    let steps = cfg.path.as_deref().map(parse_path).transpose()?;
+    let mut reader = BufReader::new(cfg.files.reader(context.input)?);
+    let mut source = String::new();
+    to_crush_error(reader.read_to_string(&mut source))?;
+    let document = match Document::parse(&source) {
+        Ok(document) => document,
+        Err(e) => return data_error(format!("xml: {}", e).as_str()),
+    };
+
+    match steps {
+        None => context
+            .output
+            .send(Value::Struct(element_struct(document.root_element())?)),
+        Some(steps) => {
+            let output = context.output.initialize(ELEMENT_TYPE.clone())?;
+            for node in select(&document, &steps) {
+                output.send(Row::new(element(node)?))?;
+            }
+            Ok(())
+        }
+    }
+}
+
+fn escape(s: &str) -> String {
+    s.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+/** Read a field of an element struct as a string, if present. */
+fn string_field(s: &Struct, name: &str) -> CrushResult<Option<String>> {
+    match s.get(name) {
+        None | Some(Value::Empty()) => Ok(None),
+        Some(Value::String(v)) => Ok(Some(v)),
+        Some(v) => argument_error(
+            format!(
+                "Expected the {} field of an element to be a string, got a value of type {}",
+                name,
+                v.value_type().to_string()
+            )
+            .as_str(),
+        ),
+    }
+}
+
+fn write_element(out: &mut dyn Write, s: &Struct, indent: usize) -> CrushResult<()> {
+    let name = mandate(
+        string_field(s, "name")?,
+        "Expected an element with a name field",
+    )?;
+    let mut attributes = Vec::new();
+    match s.get("attributes") {
+        None | Some(Value::Empty()) => {}
+        Some(Value::Dict(d)) => {
+            for (k, v) in d.elements() {
+                attributes.push((k.to_string(), v.to_string()));
+            }
+        }
+        Some(Value::Struct(a)) => {
+            for (k, v) in a.local_elements() {
+                attributes.push((k, v.to_string()));
+            }
+        }
+        Some(v) => {
+            return argument_error(
+                format!(
+                    "Expected the attributes of an element to be a dict or a struct, got a value of type {}",
+                    v.value_type().to_string()
+                )
+                .as_str(),
+            )
+        }
+    }
+    let children = match s.get("children") {
+        None | Some(Value::Empty()) => Vec::new(),
+        Some(Value::List(l)) => l.dump(),
+        Some(v) => {
+            return argument_error(
+                format!(
+                    "Expected the children of an element to be a list, got a value of type {}",
+                    v.value_type().to_string()
+                )
+                .as_str(),
+            )
+        }
+    };
+    let text = string_field(s, "text")?.unwrap_or_default();
+
+    let padding = "  ".repeat(indent);
+    to_crush_error(write!(out, "{}<{}", padding, name))?;
+    for (k, v) in attributes {
+        to_crush_error(write!(out, " {}=\"{}\"", k, escape(&v)))?;
+    }
+    if children.is_empty() && text.is_empty() {
+        return to_crush_error(writeln!(out, "/>"));
+    }
+    to_crush_error(write!(out, ">{}", escape(&text)))?;
+    if !children.is_empty() {
+        to_crush_error(writeln!(out))?;
+        for child in children {
+            match child {
+                Value::Struct(c) => write_element(out, &c, indent + 1)?,
+                v => return argument_error(
+                    format!(
+                        "Expected the children of an element to be structs, got a value of type {}",
+                        v.value_type().to_string()
+                    )
+                    .as_str(),
+                ),
+            }
+        }
+        to_crush_error(write!(out, "{}", padding))?;
+    }
+    to_crush_error(writeln!(out, "</{}>", name))
+}
+
+#[signature(
+    to,
+    can_block = true,
+    output = Unknown,
+    short = "Serialize to xml format",
+    long = "The input is an element, i.e. a struct with a name field and optionally attributes, children and text fields, like the output of xml:from. The attributes can be a dict or a struct. If no file is specified, output is returned as a BinaryStream.",
+    long = "A list, table or table stream of elements, like the output of xml:from with a path, is written as the children of a root element with the name given by the root argument.",
+    example = "xml:from pom.xml path=\"//dependency\" | xml:to root=\"dependencies\""
+)]
+struct To {
+    #[unnamed()]
+    #[description("the file to write to. If unspecified, the output is a binary_stream.")]
+    file: Files,
+    #[description("the name of the root element to write a list or stream of elements in.")]
+    root: Option<String>,
+}
+
+fn to(context: ExecutionContext) -> CrushResult<()> {
+    let cfg: To = To::parse(context.arguments, &context.printer)?;
+    let root = cfg.root;
+    let mut writer = cfg.file.writer(context.output)?;
+    to_crush_error(writeln!(
+        writer,
+        "<?xml version=\"1.0\" encoding=\"UTF-8\"?>"
+    ))?;
+    match context.input.recv()? {
+        Value::Struct(s) => write_element(writer.as_mut(), &s, 0),
+        value => {
+            let root = mandate(
+                root,
+                "xml: Specify the name of the root element to write a list or a stream in",
+            )?;
+            let children = match value {
+                Value::List(l) => l.dump(),
+                value => {
+                    let mut input = mandate(
+                        value.stream(),
+                        "Expected an element, or a list or a stream of elements",
+                    )?;
+                    let types = input.types().to_vec();
+                    let mut res = Vec::new();
+                    while let Ok(row) = input.read() {
+                        res.push(Value::Struct(row.into_struct(&types)));
+                    }
+                    res
+                }
+            };
+            let element = Struct::new(
+                vec![
+                    ("name".to_string(), Value::string(&root)),
+                    (
+                        "children".to_string(),
+                        Value::List(List::new(ValueType::Struct, children)),
+                    ),
+                ],
+                None,
+            );
+            write_element(writer.as_mut(), &element, 0)
+        }
+    }
+}
+
+pub fn declare(root: &mut ScopeLoader) -> CrushResult<()> {
+    root.create_lazy_namespace(
+        "xml",
+        Box::new(move |env| {
+            From::declare(env)?;
+            To::declare(env)?;
+            Ok(())
+        }),
+    )?;
+    Ok(())
+}
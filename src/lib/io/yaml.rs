@@ -0,0 +1,382 @@
+use crate::lang::execution_context::ExecutionContext;
+use crate::lang::{table::Row, value::Value, value::ValueType};
+use std::io::{BufReader, Read, Write};
+
+use crate::lang::argument::ArgumentHandler;
+use crate::lang::command::InputType;
+use crate::lang::command::OutputType::Unknown;
+use crate::lang::dict::Dict;
+use crate::lang::errors::{error, mandate, to_crush_error, CrushError, CrushResult};
+use crate::lang::files::Files;
+use crate::lang::scope::ScopeLoader;
+use crate::lang::table::ColumnType;
+use crate::lang::{list::List, r#struct::Struct, table::Table};
+use chrono::{DateTime, Local, NaiveDate, TimeZone, Utc};
+use signature::signature;
+use std::collections::{HashMap, HashSet};
+use std::convert::TryFrom;
+use yaml_rust::parser::{Event, MarkedEventReceiver, Parser};
+use yaml_rust::scanner::{Marker, TScalarStyle, TokenType};
+use yaml_rust::yaml::Hash;
+use yaml_rust::{Yaml, YamlEmitter};
+
+/** The common type of all the values, or any if they are of different types. */
+fn common_type(values: &[Value]) -> ValueType {
+    let types: HashSet<ValueType> = values.iter().map(|v| v.value_type()).collect();
+    if types.len() == 1 {
+        types.into_iter().next().unwrap()
+    } else {
+        ValueType::Any
+    }
+}
+
+/**
+  Strings in one of the timestamp formats of YAML are read as times. A date without a time is
+  midnight UTC.
+*/
+fn timestamp(s: &str) -> Option<Value> {
+    if let Ok(t) = DateTime::parse_from_rfc3339(s) {
+        return Some(Value::Time(t.with_timezone(&Local)));
+    }
+    if s.len() == 10 {
+        if let Ok(d) = NaiveDate::parse_from_str(s, "%Y-%m-%d") {
+            return Some(Value::Time(
+                Utc.from_utc_datetime(&d.and_hms(0, 0, 0))
+                    .with_timezone(&Local),
+            ));
+        }
+    }
+    None
+}
+
+/** A scalar, converted the way YAML resolves plain scalars. Quoted scalars are always strings. */
+fn scalar(v: String, style: TScalarStyle, tag: Option<TokenType>, timestamps: bool) -> Value {
+    if style != TScalarStyle::Plain {
+        return Value::string(&v);
+    }
+    if let Some(TokenType::Tag(handle, suffix)) = tag {
+        if handle == "!!" && suffix == "str" {
+            return Value::string(&v);
+        }
+    }
+    match Yaml::from_str(&v) {
+        Yaml::Null => Value::Empty(),
+        Yaml::Boolean(b) => Value::Bool(b),
+        Yaml::Integer(i) => Value::Integer(i as i128),
+        Yaml::Real(r) => match r.parse::<f64>() {
+            Ok(f) => Value::Float(f),
+            Err(_) => Value::string(&r),
+        },
+        _ => if timestamps { timestamp(&v) } else { None }.unwrap_or_else(|| Value::string(&v)),
+    }
+}
+
+fn sequence(mut lst: Vec<Value>) -> CrushResult<Value> {
+    let struct_types: HashSet<Vec<ColumnType>> = lst
+        .iter()
+        .flat_map(|v| match v {
+            Value::Struct(r) => vec![r.local_signature()],
+            _ => vec![],
+        })
+        .collect();
+    let list_type = common_type(&lst);
+    match (&list_type, struct_types.len()) {
+        (ValueType::Struct, 1) => {
+            let row_list = lst
+                .drain(..)
+                .map(|v| match v {
+                    Value::Struct(r) => Ok(r.to_row()),
+                    _ => error("Impossible!"),
+                })
+                .collect::<CrushResult<Vec<Row>>>()?;
+            Ok(Value::Table(Table::new(
+                struct_types.into_iter().next().unwrap(),
+                row_list,
+            )))
+        }
+        _ => Ok(Value::List(List::new(list_type, lst))),
+    }
+}
+
+fn mapping(entries: Vec<(Value, Value)>) -> CrushResult<Value> {
+    if entries.iter().all(|(k, _)| matches!(k, Value::String(_))) {
+        return Ok(Value::Struct(Struct::new(
+            entries
+                .into_iter()
+                .map(|(k, v)| (k.to_string(), v))
+                .collect(),
+            None,
+        )));
+    }
+    if let Some((k, _)) = entries.iter().find(|(k, _)| !k.value_type().is_hashable()) {
+        return error(
+            format!(
+                "Unsupported mapping key of type {}",
+                k.value_type().to_string()
+            )
+            .as_str(),
+        );
+    }
+    let (keys, values): (Vec<Value>, Vec<Value>) = entries.into_iter().unzip();
+    let dict = Dict::new(common_type(&keys), common_type(&values));
+    for (k, v) in keys.into_iter().zip(values) {
+        dict.insert(k, v)?;
+    }
+    Ok(Value::Dict(dict))
+}
+
+enum Node {
+    Sequence(Vec<Value>, usize),
+    Mapping(Vec<(Value, Value)>, Option<Value>, usize),
+}
+
+/**
+  Builds values from the events of the YAML parser, because only the events tell whether a
+  scalar was quoted.
+*/
+struct Loader {
+    timestamps: bool,
+    documents: Vec<Value>,
+    stack: Vec<Node>,
+    anchors: HashMap<usize, Value>,
+    error: Option<CrushError>,
+}
+
+impl Loader {
+    fn insert(&mut self, value: Value, anchor: usize) {
+        if anchor > 0 {
+            self.anchors.insert(anchor, value.clone());
+        }
+        match self.stack.last_mut() {
+            None => self.documents.push(value),
+            Some(Node::Sequence(values, _)) => values.push(value),
+            Some(Node::Mapping(entries, key, _)) => match key.take() {
+                None => *key = Some(value),
+                Some(k) => entries.push((k, value)),
+            },
+        }
+    }
+
+    fn event(&mut self, event: Event) -> CrushResult<()> {
+        match event {
+            Event::Scalar(v, style, anchor, tag) => {
+                let value = scalar(v, style, tag, self.timestamps);
+                self.insert(value, anchor);
+            }
+            Event::Alias(anchor) => {
+                let value = mandate(self.anchors.get(&anchor), "Unknown YAML alias")?.clone();
+                self.insert(value, 0);
+            }
+            Event::SequenceStart(anchor) => self.stack.push(Node::Sequence(Vec::new(), anchor)),
+            Event::MappingStart(anchor) => self.stack.push(Node::Mapping(Vec::new(), None, anchor)),
+            Event::SequenceEnd | Event::MappingEnd => {
+                let (value, anchor) = match mandate(self.stack.pop(), "Invalid YAML document")? {
+                    Node::Sequence(values, anchor) => (sequence(values)?, anchor),
+                    Node::Mapping(entries, _, anchor) => (mapping(entries)?, anchor),
+                };
+                self.insert(value, anchor);
+            }
+            _ => {}
+        }
+        Ok(())
+    }
+}
+
+impl MarkedEventReceiver for Loader {
+    fn on_event(&mut self, event: Event, _mark: Marker) {
+        if self.error.is_none() {
+            if let Err(e) = self.event(event) {
+                self.error = Some(e);
+            }
+        }
+    }
+}
+
+fn load(source: &str, timestamps: bool) -> CrushResult<Vec<Value>> {
+    let mut loader = Loader {
+        timestamps,
+        documents: Vec::new(),
+        stack: Vec::new(),
+        anchors: HashMap::new(),
+        error: None,
+    };
+    to_crush_error(Parser::new(source.chars()).load(&mut loader, true))?;
+    match loader.error {
+        Some(e) => Err(e),
+        None => Ok(loader.documents),
+    }
+}
+
+#[signature(
+from,
+can_block = true,
+output = Unknown,
+input = InputType::Binary,
+short = "Parse yaml format",
+long = "Sequences of mappings with the same keys become tables, other mappings become structs, or dicts if not all keys are strings. Unquoted strings in the timestamp format of YAML become times, unless timestamps=false. Quoted strings are always strings.",
+long = "Input with more than one document is returned as a list with one element per document. With stream=true, the output is a table stream with a single value column, and one row per document.",
+example = "yaml:from .gitlab-ci.yml")]
+struct From {
+    #[unnamed()]
+    files: Files,
+    #[description("output every document as a row of a table stream.")]
+    #[default(false)]
+    stream: bool,
+    #[description("read unquoted strings in the timestamp format of YAML as times.")]
+    #[default(true)]
+    timestamps: bool,
+}
+
+fn from(context: ExecutionContext) -> CrushResult<()> {
+    let cfg: From = From::parse(context.arguments, &context.printer)?;
+    let timestamps = cfg.timestamps;
+    let mut reader = BufReader::new(cfg.files.reader(context.input)?);
+    let mut s = String::new();
+    to_crush_error(reader.read_to_string(&mut s))?;
+    let mut documents = load(&s, timestamps)?;
+
+    if cfg.stream {
+        let output = context
+            .output
+            .initialize(vec![ColumnType::new("value", ValueType::Any)])?;
+        for document in documents {
+            output.send(Row::new(vec![document]))?;
+        }
+        return Ok(());
+    }
+    match documents.len() {
+        0 => context.output.send(Value::Empty()),
+        1 => context.output.send(documents.remove(0)),
+        _ => context
+            .output
+            .send(Value::List(List::new(common_type(&documents), documents))),
+    }
+}
+
+fn too_large<T>(integer: &str) -> CrushResult<T> {
+    error(format!("The integer {} is too large for yaml", integer).as_str())
+}
+
+fn to_yaml(value: Value) -> CrushResult<Yaml> {
+    match value.materialize() {
+        Value::Empty() => Ok(Yaml::Null),
+
+        Value::File(s) => Ok(Yaml::String(
+            mandate(s.to_str(), "Invalid filename")?.to_string(),
+        )),
+
+        Value::String(s) => Ok(Yaml::String(s.to_string())),
+
+        Value::Integer(i) => match i64::try_from(i) {
+            Ok(i) => Ok(Yaml::Integer(i)),
+            Err(_) => too_large(&i.to_string()),
+        },
+
+        Value::BigInteger(i) => too_large(&i.to_string()),
+
+        Value::List(l) => Ok(Yaml::Array(
+            l.dump()
+                .drain(..)
+                .map(to_yaml)
+                .collect::<CrushResult<Vec<_>>>()?,
+        )),
+
+        Value::Table(t) => {
+            let types = t.types().to_vec();
+            let structs = t
+                .rows()
+                .iter()
+                .map(|r| r.clone().into_struct(&types))
+                .map(|s| to_yaml(Value::Struct(s)))
+                .collect::<CrushResult<Vec<_>>>()?;
+            Ok(Yaml::Array(structs))
+        }
+
+        Value::Bool(b) => Ok(Yaml::Boolean(b)),
+
+        Value::Float(f) => Ok(Yaml::Real(format!("{:?}", f))),
+
+        Value::Struct(s) => {
+            let mut map = Hash::new();
+            for (k, v) in s.local_elements() {
+                map.insert(Yaml::String(k.to_string()), to_yaml(v)?);
+            }
+            Ok(Yaml::Hash(map))
+        }
+
+        Value::Dict(d) => {
+            let mut map = Hash::new();
+            for (k, v) in d.elements() {
+                map.insert(to_yaml(k)?, to_yaml(v)?);
+            }
+            Ok(Yaml::Hash(map))
+        }
+
+        Value::Duration(d) => Ok(Yaml::Integer(d.num_seconds())),
+
+        Value::Time(t) => Ok(Yaml::String(t.to_rfc3339())),
+
+        v => error(format!("Unsupported data type {}", v.value_type().to_string()).as_str()),
+    }
+}
+
+#[signature(
+to,
+can_block = true,
+output = Unknown,
+short = "Serialize to yaml format",
+long = "If no file is specified, output is returned as a BinaryStream.\n    The following Crush types are supported: File, string, integer, float, bool, list, table,\n    table_stream, struct, dict, time and duration.",
+long = "With stream=true, every element of a list, or every row of a table or table stream, is written as a separate document. Rows with a single column are written as the value of that column.",
+example = "ls | yaml:to")]
+struct To {
+    #[unnamed()]
+    file: Files,
+    #[description("write every element or row of the input as a separate document.")]
+    #[default(false)]
+    stream: bool,
+}
+
+fn to(context: ExecutionContext) -> CrushResult<()> {
+    let cfg: To = To::parse(context.arguments, &context.printer)?;
+    let mut writer = cfg.file.writer(context.output)?;
+    let value = context.input.recv()?;
+    let documents = if !cfg.stream {
+        vec![to_yaml(value)?]
+    } else if let Value::List(l) = value {
+        l.dump()
+            .drain(..)
+            .map(to_yaml)
+            .collect::<CrushResult<Vec<_>>>()?
+    } else {
+        let mut input = mandate(value.stream(), "Expected a list or a stream")?;
+        let types = input.types().to_vec();
+        let mut res = Vec::new();
+        while let Ok(row) = input.read() {
+            res.push(to_yaml(if types.len() == 1 {
+                row.into_vec().remove(0)
+            } else {
+                Value::Struct(row.into_struct(&types))
+            })?);
+        }
+        res
+    };
+    for document in documents {
+        let mut out = String::new();
+        to_crush_error(YamlEmitter::new(&mut out).dump(&document))?;
+        out.push('\n');
+        to_crush_error(writer.write_all(out.as_bytes()))?;
+    }
+    Ok(())
+}
+
+pub fn declare(root: &mut ScopeLoader) -> CrushResult<()> {
+    root.create_lazy_namespace(
+        "yaml",
+        Box::new(move |env| {
+            From::declare(env)?;
+            To::declare(env)?;
+            Ok(())
+        }),
+    )?;
+    Ok(())
+}
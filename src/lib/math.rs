@@ -6,6 +6,7 @@ use crate::lang::execution_context::ExecutionContext;
 use crate::lang::scope::Scope;
 use crate::lang::value::Value;
 use crate::lang::value::ValueType;
+use num_traits::ToPrimitive;
 
 macro_rules! math_fun {
     ($name:ident, $op:expr) => {
@@ -14,6 +15,7 @@ macro_rules! math_fun {
             let x = match context.arguments.value(0)? {
                 Value::Float(f) => f,
                 Value::Integer(i) => i as f64,
+                Value::BigInteger(i) => i.to_f64().unwrap_or(f64::NAN),
                 v => {
                     return argument_error(
                         format!("Expected a number, got a {}", v.value_type().to_string()).as_str(),
@@ -32,6 +34,7 @@ macro_rules! math_fun2 {
             let x = match context.arguments.value(0)? {
                 Value::Float(f) => f,
                 Value::Integer(i) => i as f64,
+                Value::BigInteger(i) => i.to_f64().unwrap_or(f64::NAN),
                 v => {
                     return argument_error(
                         format!("Expected a number, got a {}", v.value_type().to_string()).as_str(),
@@ -41,6 +44,7 @@ macro_rules! math_fun2 {
             let y = match context.arguments.value(1)? {
                 Value::Float(f) => f,
                 Value::Integer(i) => i as f64,
+                Value::BigInteger(i) => i.to_f64().unwrap_or(f64::NAN),
                 v => {
                     return argument_error(
                         format!("Expected a number, got a {}", v.value_type().to_string()).as_str(),
@@ -1,9 +1,14 @@
 use crate::lang::argument::ArgumentHandler;
-use crate::lang::command::OutputType::{Known, Unknown};
-use crate::lang::errors::{to_crush_error, CrushResult};
+use crate::lang::command::OutputType::Known;
+use crate::lang::errors::{argument_error, to_crush_error, CrushResult};
 use crate::lang::execution_context::{ArgumentVector, ExecutionContext};
+use crate::lang::job;
+use crate::lang::job::JobStatus;
+use crate::lang::pretty_printer::create_pretty_printer;
 use crate::lang::scope::Scope;
+use crate::lang::stream::empty_channel;
 use crate::lang::table::ColumnType;
+use crate::util::thread::{build, handle};
 use crate::util::user_map::{create_user_map, UserMap};
 use crate::{lang::table::Row, lang::value::Value, lang::value::ValueType};
 use chrono::Duration;
@@ -12,6 +17,7 @@ use nix::sys::signal;
 use nix::unistd::Pid;
 use psutil::process::State;
 use signature::signature;
+use std::convert::TryFrom;
 use std::str::FromStr;
 use users::uid_t;
 
@@ -24,6 +30,11 @@ lazy_static! {
         ColumnType::new("cpu", ValueType::Duration),
         ColumnType::new("name", ValueType::String),
     ];
+    static ref JOBS_OUTPUT_TYPE: Vec<ColumnType> = vec![
+        ColumnType::new("id", ValueType::Integer),
+        ColumnType::new("status", ValueType::String),
+        ColumnType::new("source", ValueType::String),
+    ];
 }
 
 fn state_name(s: psutil::process::State) -> &'static str {
@@ -81,6 +92,127 @@ struct Kill {
     signal: String,
 }
 
+#[signature(
+    jobs,
+    can_block = false,
+    short = "List all jobs running in the background",
+    long = "Jobs are started in the background by ending them with a `&`, or by using the bg command.")]
+struct Jobs {}
+
+fn jobs(context: ExecutionContext) -> CrushResult<()> {
+    let output = context.output.initialize(JOBS_OUTPUT_TYPE.clone())?;
+    let list = job::jobs().list();
+    for (id, status, source) in list {
+        output.send(Row::new(vec![
+            Value::Integer(id as i128),
+            Value::string(&status.to_string()),
+            Value::String(source),
+        ]))?;
+    }
+    Ok(())
+}
+
+#[signature(
+    fg,
+    can_block = true,
+    short = "Wait for a background job to finish",
+    output = Known(ValueType::Empty),
+    long = "The job is removed from the job table and the prompt does not return until it is done. A job that was stopped using ^Z is continued first.",
+    example = "find / | count &\n    fg")]
+struct Fg {
+    #[description("the id of the job to wait for. Defaults to the most recently started job.")]
+    id: Option<usize>,
+}
+
+fn fg(context: ExecutionContext) -> CrushResult<()> {
+    let cfg: Fg = Fg::parse(context.arguments, &context.printer)?;
+    let (_, _, handle) = job::jobs().take(cfg.id)?;
+    handle.join(&context.printer);
+    context.output.send(Value::Empty())
+}
+
+#[signature(
+    bg,
+    can_block = false,
+    short = "Run a command in the background, or continue a stopped job there",
+    output = Known(ValueType::Integer),
+    long = "Running a command is equivalent to ending a job with a `&`, but also works from inside of closures. Given the id of a job that was stopped using ^Z, or nothing, the job or the most recently stopped job is continued in the background without the terminal. Returns the id of the job.",
+    example = "bg {find / | where {size > 1_000_000_000}}")]
+struct Bg {
+    #[description("the command to run, or the id of the stopped job to continue.")]
+    job: Option<Value>,
+}
+
+fn bg(context: ExecutionContext) -> CrushResult<()> {
+    let cfg: Bg = Bg::parse(context.arguments, &context.printer)?;
+    let command = match cfg.job {
+        Some(Value::Command(command)) => command,
+        Some(Value::Integer(id)) => {
+            let id = job::jobs().resume(Some(to_crush_error(usize::try_from(id))?))?;
+            return context.output.send(Value::Integer(id as i128));
+        }
+        None => {
+            let id = job::jobs().resume(None)?;
+            return context.output.send(Value::Integer(id as i128));
+        }
+        Some(_) => return argument_error("Expected a command or the id of a stopped job"),
+    };
+    let (printer, failed) = context.printer.tracked();
+    let source = format!("bg {}", command.signature().trim());
+    let job_context = ExecutionContext {
+        input: empty_channel(),
+        output: create_pretty_printer(printer.clone()),
+        arguments: vec![],
+        env: context.env.clone(),
+        this: None,
+        printer: printer.clone(),
+    };
+    let h = handle(build("bg").spawn(move || printer.handle_error(command.invoke(job_context))));
+    let id = job::jobs().add(&source, h, failed);
+    context.output.send(Value::Integer(id as i128))
+}
+
+#[signature(
+    wait,
+    can_block = true,
+    short = "Wait for background jobs to finish",
+    output = Known(ValueType::Empty),
+    long = "The jobs are removed from the job table once done. Stopped jobs are not waited for, use fg or bg to continue them.")]
+struct Wait {
+    #[unnamed()]
+    #[description("the ids of the jobs to wait for. Defaults to all jobs that aren't stopped.")]
+    id: Vec<usize>,
+}
+
+fn wait(context: ExecutionContext) -> CrushResult<()> {
+    let cfg: Wait = Wait::parse(context.arguments, &context.printer)?;
+    let stopped = job::jobs()
+        .list()
+        .into_iter()
+        .filter(|(_, status, _)| *status == JobStatus::Stopped)
+        .map(|(id, _, _)| id)
+        .collect::<Vec<_>>();
+    let ids = if cfg.id.is_empty() {
+        job::jobs()
+            .ids()
+            .into_iter()
+            .filter(|id| !stopped.contains(id))
+            .collect()
+    } else {
+        cfg.id
+    };
+    if let Some(id) = ids.iter().find(|id| stopped.contains(id)) {
+        return argument_error(
+            format!("Job {} is stopped, use fg or bg to continue it", id).as_str(),
+        );
+    }
+    for id in ids {
+        let (_, _, handle) = job::jobs().take(Some(id))?;
+        handle.join(&context.printer);
+    }
+    context.output.send(Value::Empty())
+}
+
 fn kill(context: ExecutionContext) -> CrushResult<()> {
     let sig: Kill = Kill::parse(context.arguments, &context.printer)?;
     for pid in sig.pid {
@@ -120,9 +252,13 @@ pub fn declare(root: &Scope) -> CrushResult<()> {
 
     * cpu:duration the amount of CPU time this process has used since its creation
 
-    * name:string the process name"#), Unknown)?;
+    * name:string the process name"#), Known(ValueType::TableStream(PS_OUTPUT_TYPE.clone())))?;
 
             Kill::declare(env)?;
+            Jobs::declare(env)?;
+            Fg::declare(env)?;
+            Bg::declare(env)?;
+            Wait::declare(env)?;
             Ok(())
         }))?;
     root.r#use(&e);
@@ -0,0 +1,304 @@
+use crate::lang::argument::ArgumentHandler;
+use crate::lang::command::InputType;
+use crate::lang::command::OutputType::Known;
+use crate::lang::errors::{argument_error, data_error, error, CrushResult};
+use crate::lang::execution_context::ExecutionContext;
+use crate::lang::list::List;
+use crate::lang::stream::{Stream, ValueReceiver};
+use crate::lang::table::{ColumnType, ColumnVec};
+use crate::lang::value::{Field, Value, ValueType};
+use crate::lib::stream::sort::compare;
+use num_traits::ToPrimitive;
+use signature::signature;
+use std::collections::HashSet;
+
+/** Find the column to aggregate, which may be left out if the input only has one. */
+fn column(types: &[ColumnType], field: &Option<Field>) -> CrushResult<usize> {
+    match field {
+        None => {
+            if types.len() == 1 {
+                Ok(0)
+            } else {
+                error("Specify which column to operate on")
+            }
+        }
+        Some(field) => types.find(field),
+    }
+}
+
+fn input(input: &ValueReceiver, field: &Option<Field>) -> CrushResult<(Stream, usize)> {
+    match input.recv()?.stream() {
+        Some(input) => {
+            let idx = column(input.types(), field)?;
+            Ok((input, idx))
+        }
+        None => error("Expected a stream"),
+    }
+}
+
+fn check_numeric(name: &str, cell_type: &ValueType) -> CrushResult<()> {
+    match cell_type {
+        ValueType::Integer | ValueType::Float | ValueType::Duration => Ok(()),
+        t => argument_error(
+            format!(
+                "Can't calculate {} of elements of type {}",
+                name,
+                t.to_string()
+            )
+            .as_str(),
+        ),
+    }
+}
+
+/** Read all values of a column, sorted in the same order as sort uses, i.e. with NaN last. */
+fn sorted(mut input: Stream, idx: usize) -> Vec<Value> {
+    let mut values = Vec::new();
+    while let Ok(row) = input.read() {
+        values.push(row.into_vec().remove(idx));
+    }
+    values.sort_by(compare);
+    values
+}
+
+/** The value halfway between two values, rounded down for integers like avg does. */
+fn midpoint(a: &Value, b: &Value) -> CrushResult<Value> {
+    match (a, b) {
+        (Value::Float(a), Value::Float(b)) => Ok(Value::Float((a + b) / 2.0)),
+        (Value::Duration(a), Value::Duration(b)) => Ok(Value::Duration((*a + *b) / 2)),
+        _ => match (a.big_integer(), b.big_integer()) {
+            (Some(a), Some(b)) => Ok(Value::from((a + b) / 2)),
+            _ => data_error("Invalid cell value"),
+        },
+    }
+}
+
+#[signature(
+    median,
+    can_block = true,
+    input = InputType::Column(vec![ValueType::Integer, ValueType::Float, ValueType::Duration]),
+    short = "Calculate the median of the specified column across all rows",
+    long = "For an even number of rows, the median is the average of the two middle values.",
+    example = "ps | median ^cpu"
+)]
+pub struct Median {
+    #[description("the column to operate on. May be left out if the input has a single column.")]
+    column: Option<Field>,
+}
+
+pub fn median(context: ExecutionContext) -> CrushResult<()> {
+    let cfg: Median = Median::parse(context.arguments, &context.printer)?;
+    let (input, idx) = input(&context.input, &cfg.column)?;
+    check_numeric("median", &input.types()[idx].cell_type)?;
+    let values = sorted(input, idx);
+    let middle = values.len() / 2;
+    match values.len() {
+        0 => error("Can't calculate median of an empty stream"),
+        n if n % 2 == 1 => context.output.send(values[middle].clone()),
+        _ => context
+            .output
+            .send(midpoint(&values[middle - 1], &values[middle])?),
+    }
+}
+
+#[signature(
+    percentile,
+    can_block = true,
+    input = InputType::Table,
+    short = "Pick the specified percentile of a column across all rows",
+    long = "This uses the nearest rank method, so the result is always one of the input values, and the 50th percentile of an even number of rows is the lower of the two middle values.",
+    example = "ps | percentile 95 ^cpu"
+)]
+pub struct Percentile {
+    #[description("the percentile to pick, between 0 and 100.")]
+    percentile: Value,
+    #[description("the column to operate on. May be left out if the input has a single column.")]
+    column: Option<Field>,
+}
+
+pub fn percentile(context: ExecutionContext) -> CrushResult<()> {
+    let cfg: Percentile = Percentile::parse(context.arguments, &context.printer)?;
+    let percentile = match cfg.percentile {
+        Value::Integer(i) => i as f64,
+        Value::Float(f) => f,
+        v => {
+            return argument_error(
+                format!(
+                    "Expected the percentile to be a number, got a value of type {}",
+                    v.value_type().to_string()
+                )
+                .as_str(),
+            )
+        }
+    };
+    if !(0.0..=100.0).contains(&percentile) {
+        return argument_error("The percentile must be between 0 and 100");
+    }
+    let (input, idx) = input(&context.input, &cfg.column)?;
+    if !input.types()[idx].cell_type.is_comparable() {
+        return argument_error("Can't pick a percentile of values that can't be compared");
+    }
+    let values = sorted(input, idx);
+    if values.is_empty() {
+        return error("Can't pick a percentile of an empty stream");
+    }
+    let rank = (percentile / 100.0 * values.len() as f64).ceil() as usize;
+    context.output.send(values[rank.max(1) - 1].clone())
+}
+
+#[signature(
+    stddev,
+    can_block = true,
+    output = Known(ValueType::Float),
+    input = InputType::Column(vec![ValueType::Integer, ValueType::Float]),
+    short = "Calculate the standard deviation of the specified column across all rows",
+    example = "ps | stddev ^cpu"
+)]
+pub struct Stddev {
+    #[description("the column to operate on. May be left out if the input has a single column.")]
+    column: Option<Field>,
+    #[description(
+        "calculate the sample standard deviation instead of the population standard deviation."
+    )]
+    #[default(false)]
+    sample: bool,
+}
+
+pub fn stddev(context: ExecutionContext) -> CrushResult<()> {
+    let cfg: Stddev = Stddev::parse(context.arguments, &context.printer)?;
+    let (mut input, idx) = input(&context.input, &cfg.column)?;
+    match &input.types()[idx].cell_type {
+        ValueType::Integer | ValueType::Float => {}
+        t => {
+            return argument_error(
+                format!(
+                    "Can't calculate standard deviation of elements of type {}",
+                    t.to_string()
+                )
+                .as_str(),
+            )
+        }
+    }
+    /* Welford's algorithm, which doesn't lose precision when the mean is large. */
+    let mut count = 0usize;
+    let mut mean = 0.0;
+    let mut squares = 0.0;
+    while let Ok(row) = input.read() {
+        let x = match &row.cells()[idx] {
+            Value::Float(f) => *f,
+            Value::Integer(i) => *i as f64,
+            v => match v.big_integer() {
+                Some(i) => i.to_f64().unwrap_or(f64::NAN),
+                None => return data_error("Invalid cell value"),
+            },
+        };
+        count += 1;
+        let delta = x - mean;
+        mean += delta / count as f64;
+        squares += delta * (x - mean);
+    }
+    let divisor = if cfg.sample { count.saturating_sub(1) } else { count };
+    match (count, divisor) {
+        (0, _) => error("Can't calculate standard deviation of an empty stream"),
+        (_, 0) => error("Can't calculate sample standard deviation of a single row"),
+        _ => context
+            .output
+            .send(Value::Float((squares / divisor as f64).sqrt())),
+    }
+}
+
+#[signature(
+    first,
+    can_block = true,
+    input = InputType::Table,
+    short = "The value of the specified column in the first row",
+    example = "ps | group ^user pid={first ^pid}"
+)]
+pub struct First {
+    #[description("the column to operate on. May be left out if the input has a single column.")]
+    column: Option<Field>,
+}
+
+pub fn first(context: ExecutionContext) -> CrushResult<()> {
+    let cfg: First = First::parse(context.arguments, &context.printer)?;
+    let (mut input, idx) = input(&context.input, &cfg.column)?;
+    match input.read() {
+        Ok(row) => context.output.send(row.into_vec().remove(idx)),
+        Err(_) => error("Can't pick the first value of an empty stream"),
+    }
+}
+
+#[signature(
+    last,
+    can_block = true,
+    input = InputType::Table,
+    short = "The value of the specified column in the last row",
+    example = "ps | group ^user pid={last ^pid}"
+)]
+pub struct Last {
+    #[description("the column to operate on. May be left out if the input has a single column.")]
+    column: Option<Field>,
+}
+
+pub fn last(context: ExecutionContext) -> CrushResult<()> {
+    let cfg: Last = Last::parse(context.arguments, &context.printer)?;
+    let (mut input, idx) = input(&context.input, &cfg.column)?;
+    let mut res = None;
+    while let Ok(row) = input.read() {
+        res = Some(row.into_vec().remove(idx));
+    }
+    match res {
+        Some(value) => context.output.send(value),
+        None => error("Can't pick the last value of an empty stream"),
+    }
+}
+
+#[signature(
+    collect,
+    can_block = true,
+    input = InputType::Table,
+    short = "Gather the values of the specified column into a list",
+    example = "ps | group ^user pids={collect ^pid}"
+)]
+pub struct Collect {
+    #[description("the column to operate on. May be left out if the input has a single column.")]
+    column: Option<Field>,
+}
+
+pub fn collect(context: ExecutionContext) -> CrushResult<()> {
+    let cfg: Collect = Collect::parse(context.arguments, &context.printer)?;
+    let (mut input, idx) = input(&context.input, &cfg.column)?;
+    let element_type = input.types()[idx].cell_type.clone();
+    let mut values = Vec::new();
+    while let Ok(row) = input.read() {
+        values.push(row.into_vec().remove(idx));
+    }
+    context
+        .output
+        .send(Value::List(List::new(element_type, values)))
+}
+
+#[signature(
+    distinct_count,
+    can_block = true,
+    output = Known(ValueType::Integer),
+    input = InputType::Table,
+    short = "Count the number of distinct values of the specified column",
+    example = "ps | group ^user commands={distinct_count ^name}"
+)]
+pub struct DistinctCount {
+    #[description("the column to operate on. May be left out if the input has a single column.")]
+    column: Option<Field>,
+}
+
+pub fn distinct_count(context: ExecutionContext) -> CrushResult<()> {
+    let cfg: DistinctCount = DistinctCount::parse(context.arguments, &context.printer)?;
+    let (mut input, idx) = input(&context.input, &cfg.column)?;
+    if !input.types()[idx].cell_type.is_hashable() {
+        return argument_error("Can't count distinct values of a column that isn't hashable");
+    }
+    let mut seen = HashSet::new();
+    while let Ok(row) = input.read() {
+        seen.insert(row.into_vec().remove(idx));
+    }
+    context.output.send(Value::Integer(seen.len() as i128))
+}
@@ -0,0 +1,50 @@
+use crate::lang::argument::ArgumentHandler;
+use crate::lang::errors::{argument_error, error, CrushResult};
+use crate::lang::execution_context::ExecutionContext;
+use crate::lang::table::{ColumnType, Row, Table};
+use crate::lang::value::{Value, ValueType};
+use signature::signature;
+
+#[signature(
+    batch,
+    can_block = true,
+    short = "Group consecutive rows of the io into tables",
+    long = "The output has a single column named batch, holding a table of up to the specified number of rows. Every batch is full except possibly the last one, and each batch is passed on as soon as it is full.",
+    example = "find . | batch 100 | select size={val batch | sum ^size}"
+)]
+pub struct Batch {
+    #[description("the number of rows in each batch.")]
+    rows: usize,
+}
+
+pub fn batch(context: ExecutionContext) -> CrushResult<()> {
+    let cfg: Batch = Batch::parse(context.arguments, &context.printer)?;
+    if cfg.rows == 0 {
+        return argument_error("Batches must hold at least one row");
+    }
+    match context.input.recv()?.stream() {
+        Some(mut input) => {
+            let types = input.types().to_vec();
+            let output = context.output.initialize(vec![ColumnType::new(
+                "batch",
+                ValueType::Table(types.clone()),
+            )])?;
+            let mut rows = Vec::with_capacity(cfg.rows);
+            while let Ok(row) = input.read() {
+                rows.push(row);
+                if rows.len() == cfg.rows {
+                    let full = std::mem::replace(&mut rows, Vec::with_capacity(cfg.rows));
+                    output.send(Row::new(vec![Value::Table(Table::new(
+                        types.clone(),
+                        full,
+                    ))]))?;
+                }
+            }
+            if !rows.is_empty() {
+                output.send(Row::new(vec![Value::Table(Table::new(types, rows))]))?;
+            }
+            Ok(())
+        }
+        None => error("Expected a stream"),
+    }
+}
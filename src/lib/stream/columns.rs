@@ -0,0 +1,145 @@
+use crate::lang::argument::ArgumentHandler;
+use crate::lang::command::InputType;
+use crate::lang::errors::{argument_error, error, CrushResult};
+use crate::lang::execution_context::ExecutionContext;
+use crate::lang::ordered_string_map::OrderedStringMap;
+use crate::lang::stream::{CrushStream, ValueSender};
+use crate::lang::table::{ColumnType, ColumnVec, Row};
+use crate::lang::value::{Field, Value};
+use signature::signature;
+
+/**
+  Output every row of the input with its cells picked from the input columns with the
+  specified indices, in that order.
+*/
+fn project(
+    indices: Vec<usize>,
+    output_type: Vec<ColumnType>,
+    input: &mut dyn CrushStream,
+    sender: ValueSender,
+) -> CrushResult<()> {
+    let output = sender.initialize(output_type)?;
+    while let Ok(row) = input.read() {
+        let mut cells = row.into_vec();
+        output.send(Row::new(
+            indices
+                .iter()
+                .map(|idx| std::mem::replace(&mut cells[*idx], Value::Empty()))
+                .collect(),
+        ))?;
+    }
+    Ok(())
+}
+
+fn find(types: &[ColumnType], fields: &[Field]) -> CrushResult<Vec<usize>> {
+    let mut indices: Vec<usize> = Vec::new();
+    for field in fields {
+        let idx = types.find(field)?;
+        if indices.contains(&idx) {
+            return argument_error(
+                format!("Column {} specified more than once", types[idx].name).as_str(),
+            );
+        }
+        indices.push(idx);
+    }
+    Ok(indices)
+}
+
+#[signature(
+    drop,
+    can_block = true,
+    input = InputType::Table,
+    short = "Remove the specified columns from the io",
+    long = "This is the opposite of select, all columns except the specified ones are passed on.",
+    example = "ps | drop ^ppid ^cpu"
+)]
+pub struct Drop {
+    #[unnamed()]
+    #[description("the columns to remove.")]
+    columns: Vec<Field>,
+}
+
+pub fn drop(context: ExecutionContext) -> CrushResult<()> {
+    let cfg: Drop = Drop::parse(context.arguments, &context.printer)?;
+    match context.input.recv()?.stream() {
+        Some(mut input) => {
+            let types = input.types().to_vec();
+            let dropped = find(&types, &cfg.columns)?;
+            let indices = (0..types.len())
+                .filter(|idx| !dropped.contains(idx))
+                .collect::<Vec<_>>();
+            let output_type = indices.iter().map(|idx| types[*idx].clone()).collect();
+            project(indices, output_type, input.as_mut(), context.output)
+        }
+        None => error("Expected a stream"),
+    }
+}
+
+#[signature(
+    rename,
+    can_block = true,
+    input = InputType::Table,
+    short = "Rename columns of the io",
+    long = "The cells and types of the renamed columns are unchanged.",
+    example = "ps | rename user=\"owner\" cpu=\"load\""
+)]
+pub struct Rename {
+    #[named()]
+    #[description("the current name of each column to rename, and its new name.")]
+    names: OrderedStringMap<String>,
+}
+
+pub fn rename(context: ExecutionContext) -> CrushResult<()> {
+    let cfg: Rename = Rename::parse(context.arguments, &context.printer)?;
+    match context.input.recv()?.stream() {
+        Some(mut input) => {
+            let mut output_type = input.types().to_vec();
+            for (old, new) in cfg.names.iter() {
+                let idx = input.types().find_str(old)?;
+                output_type[idx] = ColumnType::new(new, output_type[idx].cell_type.clone());
+            }
+            for (idx, column) in output_type.iter().enumerate() {
+                if output_type[..idx].iter().any(|c| c.name == column.name) {
+                    return argument_error(
+                        format!("Multiple columns would be named {}", column.name).as_str(),
+                    );
+                }
+            }
+            let indices = (0..output_type.len()).collect();
+            project(indices, output_type, input.as_mut(), context.output)
+        }
+        None => error("Expected a stream"),
+    }
+}
+
+#[signature(
+    reorder,
+    can_block = true,
+    input = InputType::Table,
+    short = "Move the specified columns to the front of the io",
+    long = "The specified columns come first, in the order given, followed by the remaining columns in their original order.",
+    example = "ps | reorder ^name ^pid"
+)]
+pub struct Reorder {
+    #[unnamed()]
+    #[description("the columns to move to the front.")]
+    columns: Vec<Field>,
+}
+
+pub fn reorder(context: ExecutionContext) -> CrushResult<()> {
+    let cfg: Reorder = Reorder::parse(context.arguments, &context.printer)?;
+    match context.input.recv()?.stream() {
+        Some(mut input) => {
+            let types = input.types().to_vec();
+            let mut indices = find(&types, &cfg.columns)?;
+            for idx in 0..types.len() {
+                if !indices.contains(&idx) {
+                    indices.push(idx);
+                }
+            }
+            let output_type = indices.iter().map(|idx| types[*idx].clone()).collect();
+            project(indices, output_type, input.as_mut(), context.output)
+        }
+        None => error("Expected a stream"),
+    }
+}
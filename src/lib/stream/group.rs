@@ -1,5 +1,5 @@
 use crate::lang::argument::ArgumentHandler;
-use crate::lang::command::Command;
+use crate::lang::command::{Command, InputType};
 use crate::lang::errors::{mandate, CrushResult};
 use crate::lang::execution_context::ExecutionContext;
 use crate::lang::job::JobJoinHandle;
@@ -23,6 +23,7 @@ use std::collections::HashMap;
 #[signature(
     group,
     can_block = true,
+    input = InputType::Table,
     short = "Group stream by the specified column(s)",
     example = "find . | group ^user ^type file_count={count} size={sum ^size}"
 )]
@@ -2,24 +2,70 @@ use crate::lang::argument::Argument;
 use crate::lang::errors::argument_error;
 use crate::lang::errors::CrushError;
 use crate::lang::errors::CrushResult;
-use crate::lang::execution_context::{ArgumentVector, ExecutionContext};
-use crate::lang::printer::Printer;
-use crate::lang::r#struct::Struct;
+use crate::lang::execution_context::ExecutionContext;
 use crate::lang::stream::CrushStream;
 use crate::lang::stream::OutputStream;
 use crate::lang::table::ColumnType;
 use crate::lang::table::ColumnVec;
 use crate::lang::table::Row;
+use crate::lang::value::Field;
 use crate::lang::value::Value;
 use crate::lang::value::ValueType;
 use crate::util::replace::Replace;
+use std::collections::hash_map::Entry;
 use std::collections::HashMap;
 
+#[derive(Clone, Copy, PartialEq)]
+enum JoinType {
+    Inner,
+    Left,
+    Right,
+    Full,
+    Semi,
+    Anti,
+}
+
+impl JoinType {
+    fn parse(name: &str) -> CrushResult<JoinType> {
+        Ok(match name {
+            "inner" => JoinType::Inner,
+            "left" => JoinType::Left,
+            "right" => JoinType::Right,
+            "full" => JoinType::Full,
+            "semi" => JoinType::Semi,
+            "anti" => JoinType::Anti,
+            _ => {
+                return argument_error(
+                    format!(
+                        "Unknown join type {}, expected inner, left, right, full, semi or anti",
+                        name
+                    )
+                    .as_str(),
+                )
+            }
+        })
+    }
+
+    /** Semi and anti joins only filter the left side, all other joins add the right columns. */
+    fn has_right_columns(self) -> bool {
+        !matches!(self, JoinType::Semi | JoinType::Anti)
+    }
+}
+
 pub struct Config {
-    left_table_idx: usize,
-    right_table_idx: usize,
-    left_column_idx: usize,
-    right_column_idx: usize,
+    join_type: JoinType,
+    left_types: Vec<ColumnType>,
+    right_types: Vec<ColumnType>,
+    left_keys: Vec<usize>,
+    right_keys: Vec<usize>,
+    suffix: String,
+}
+
+struct Options {
+    join_type: JoinType,
+    with: Option<Value>,
+    suffix: String,
+    keys: Vec<(Field, Field)>,
 }
 
 pub fn get_sub_type(cell_type: &ValueType) -> Result<&[ColumnType], CrushError> {
@@ -29,9 +75,10 @@ pub fn get_sub_type(cell_type: &ValueType) -> Result<&[ColumnType], CrushError>
     }
 }
 
-pub fn guess_tables(
-    input_type: &[ColumnType],
-) -> Result<(usize, usize, &[ColumnType], &[ColumnType]), CrushError> {
+/** The indices and column types of the left and right table columns. */
+type Tables<'a> = (usize, usize, &'a [ColumnType], &'a [ColumnType]);
+
+pub fn guess_tables(input_type: &[ColumnType]) -> Result<Tables<'_>, CrushError> {
     let tables: Vec<(usize, &Vec<ColumnType>)> = input_type
         .iter()
         .enumerate()
@@ -60,154 +107,291 @@ fn scan_table(
     column: &str,
     input_type: &[ColumnType],
 ) -> Result<(usize, usize), CrushError> {
-    let table_idx = input_type.find_str(&table.to_string())?;
-    let column_idx =
-        get_sub_type(&input_type[table_idx].cell_type)?.find_str(&column.to_string())?;
+    let table_idx = input_type.find_str(table)?;
+    let column_idx = get_sub_type(&input_type[table_idx].cell_type)?.find_str(column)?;
     Ok((table_idx, column_idx))
 }
 
-fn parse(input_type: &[ColumnType], arguments: Vec<Argument>) -> Result<Config, CrushError> {
-    arguments.check_len(2)?;
-
-    match (&arguments[0].value, &arguments[1].value) {
-        (Value::Field(l), Value::Field(r)) => {
-            let config = match (l.len(), r.len()) {
-                (1, 1) => {
-                    let (left_table_idx, right_table_idx, left_types, right_types) =
-                        guess_tables(&input_type)?;
-
-                    Config {
-                        left_table_idx,
-                        right_table_idx,
-                        left_column_idx: left_types.find(&l)?,
-                        right_column_idx: right_types.find(&r)?,
-                    }
-                }
-                (2, 2) => {
-                    let (left_table_idx, left_column_idx) =
-                        scan_table(l[0].as_ref(), l[1].as_ref(), &input_type)?;
-
-                    let (right_table_idx, right_column_idx) =
-                        scan_table(r[0].as_ref(), r[1].as_ref(), &input_type)?;
+fn parse_options(arguments: Vec<Argument>) -> CrushResult<Options> {
+    let mut join_type = JoinType::Inner;
+    let mut with = None;
+    let mut suffix = "_right".to_string();
+    let mut fields = Vec::new();
+    for a in arguments {
+        match (a.argument_type.as_deref(), a.value) {
+            (None, Value::Field(f)) => fields.push(f),
+            (Some("type"), Value::String(s)) => join_type = JoinType::parse(&s)?,
+            (Some("with"), v) => with = Some(v),
+            (Some("suffix"), Value::String(s)) => suffix = s.to_string(),
+            _ => return argument_error("Expected arguments like ^table1:col ^table2:col"),
+        }
+    }
+    if fields.is_empty() || fields.len() % 2 != 0 {
+        return argument_error("Expected one or more pairs of left and right key columns");
+    }
+    let mut keys = Vec::new();
+    let mut fields = fields.drain(..);
+    while let (Some(l), Some(r)) = (fields.next(), fields.next()) {
+        keys.push((l, r));
+    }
+    Ok(Options {
+        join_type,
+        with,
+        suffix,
+        keys,
+    })
+}
 
-                    if left_table_idx == right_table_idx {
-                        return argument_error("Left and right table can't be the same");
-                    }
+/**
+  Find the two tables to join in the fields of the input struct, and the key columns in each
+  table. Either all keys name their table, like ^l:name, or the struct must contain exactly two
+  tables.
+*/
+fn find_tables(
+    input_type: &[ColumnType],
+    keys: &[(Field, Field)],
+) -> CrushResult<(usize, usize, Vec<usize>, Vec<usize>)> {
+    if keys.iter().all(|(l, r)| l.len() == 1 && r.len() == 1) {
+        let (left_table_idx, right_table_idx, left_types, right_types) = guess_tables(input_type)?;
+        let mut left_keys = Vec::new();
+        let mut right_keys = Vec::new();
+        for (l, r) in keys {
+            left_keys.push(left_types.find(l)?);
+            right_keys.push(right_types.find(r)?);
+        }
+        return Ok((left_table_idx, right_table_idx, left_keys, right_keys));
+    }
 
-                    Config {
-                        left_table_idx,
-                        right_table_idx,
-                        left_column_idx,
-                        right_column_idx,
-                    }
-                }
-                _ => {
-                    return argument_error(
-                        "Expected both fields on the form %table.column or %column",
-                    )
-                }
-            };
+    let mut tables = None;
+    let mut left_keys = Vec::new();
+    let mut right_keys = Vec::new();
+    for (l, r) in keys {
+        if l.len() != 2 || r.len() != 2 {
+            return argument_error("Expected all fields on the form ^table:column or ^column");
+        }
+        let (left_table_idx, left_column_idx) =
+            scan_table(l[0].as_ref(), l[1].as_ref(), input_type)?;
+        let (right_table_idx, right_column_idx) =
+            scan_table(r[0].as_ref(), r[1].as_ref(), input_type)?;
+        if left_table_idx == right_table_idx {
+            return argument_error("Left and right table can't be the same");
+        }
+        match tables {
+            None => tables = Some((left_table_idx, right_table_idx)),
+            Some(t) if t == (left_table_idx, right_table_idx) => {}
+            Some(_) => return argument_error("All keys must refer to the same two tables"),
+        }
+        left_keys.push(left_column_idx);
+        right_keys.push(right_column_idx);
+    }
+    let (left_table_idx, right_table_idx) = tables.unwrap();
+    Ok((left_table_idx, right_table_idx, left_keys, right_keys))
+}
 
-            let r_type = &get_sub_type(&input_type[config.right_table_idx].cell_type)?
-                [config.right_column_idx]
-                .cell_type;
-            let l_type = &get_sub_type(&input_type[config.left_table_idx].cell_type)?
-                [config.left_column_idx]
-                .cell_type;
-            if r_type != l_type {
-                return argument_error("Cannot join two columns of different types");
-            }
-            if !r_type.is_hashable() {
-                argument_error(
-                    "Cannot join on this column type. (It is either mutable or not comparable)",
-                )
-            } else {
-                Ok(config)
-            }
+fn check_keys(cfg: &Config) -> CrushResult<()> {
+    for (l, r) in cfg.left_keys.iter().zip(cfg.right_keys.iter()) {
+        let l_type = &cfg.left_types[*l].cell_type;
+        let r_type = &cfg.right_types[*r].cell_type;
+        if r_type != l_type {
+            return argument_error("Cannot join two columns of different types");
+        }
+        if !r_type.is_hashable() {
+            return argument_error(
+                "Cannot join on this column type. (It is either mutable or not comparable)",
+            );
         }
-        _ => argument_error("Expected arguments like %table1.col == %table2.col"),
     }
+    Ok(())
+}
+
+fn key(row: &Row, columns: &[usize]) -> Vec<Value> {
+    columns
+        .iter()
+        .map(|idx| row.cells()[*idx].clone())
+        .collect()
 }
 
-fn combine(mut l: Row, r: Row, cfg: &Config) -> Row {
-    for (idx, c) in r.into_vec().drain(..).enumerate() {
-        if idx != cfg.right_column_idx {
-            l.push(c);
+/** Append the non-key cells of the right row to the left row, or empty cells if there is none. */
+fn combine(mut l: Row, r: Option<&Row>, cfg: &Config) -> Row {
+    for idx in 0..cfg.right_types.len() {
+        if !cfg.right_keys.contains(&idx) {
+            l.push(r.map(|r| r.cells()[idx].clone()).unwrap_or(Value::Empty()));
         }
     }
     l
 }
 
+/** A row for a right row without a match. The left key columns are filled in from the right row. */
+fn right_only(r: &Row, cfg: &Config) -> Row {
+    let mut cells = vec![Value::Empty(); cfg.left_types.len()];
+    for (l, r_idx) in cfg.left_keys.iter().zip(cfg.right_keys.iter()) {
+        cells[*l] = r.cells()[*r_idx].clone();
+    }
+    combine(Row::new(cells), Some(r), cfg)
+}
+
+struct Bucket {
+    rows: Vec<Row>,
+    matched: bool,
+}
+
+/**
+  Read the right side into a hash table and stream the left side past it, so that the left side
+  may be arbitrarily large. Output rows are in the order of the left side, followed by unmatched
+  rows of the right side for right and full joins.
+*/
 fn do_join(
     cfg: &Config,
     l: &mut dyn CrushStream,
     r: &mut dyn CrushStream,
     output: &OutputStream,
-    printer: &Printer,
 ) -> CrushResult<()> {
-    let mut l_data: HashMap<Value, Row> = HashMap::new();
-    while let Ok(row) = l.read() {
-        l_data.insert(row.cells()[cfg.left_column_idx].clone(), row);
+    let mut right: HashMap<Vec<Value>, Bucket> = HashMap::new();
+    let mut order = Vec::new();
+    while let Ok(row) = r.read() {
+        let bucket = match right.entry(key(&row, &cfg.right_keys)) {
+            Entry::Occupied(e) => e.into_mut(),
+            Entry::Vacant(e) => {
+                order.push(e.key().clone());
+                e.insert(Bucket {
+                    rows: Vec::new(),
+                    matched: false,
+                })
+            }
+        };
+        bucket.rows.push(row);
     }
 
-    while let Ok(r_row) = r.read() {
-        l_data
-            .remove(&r_row.cells()[cfg.right_column_idx])
-            .map(|l_row| {
-                printer.handle_error(output.send(combine(l_row, r_row, cfg)));
-            });
+    while let Ok(l_row) = l.read() {
+        match right.get_mut(&key(&l_row, &cfg.left_keys)) {
+            Some(bucket) => {
+                bucket.matched = true;
+                match cfg.join_type {
+                    JoinType::Semi => output.send(l_row)?,
+                    JoinType::Anti => {}
+                    _ => {
+                        for r_row in &bucket.rows {
+                            output.send(combine(l_row.clone(), Some(r_row), cfg))?;
+                        }
+                    }
+                }
+            }
+            None => match cfg.join_type {
+                JoinType::Left | JoinType::Full => output.send(combine(l_row, None, cfg))?,
+                JoinType::Anti => output.send(l_row)?,
+                _ => {}
+            },
+        }
     }
-    Ok(())
-}
 
-pub fn run(
-    config: Config,
-    row: Struct,
-    output: OutputStream,
-    printer: &Printer,
-) -> CrushResult<()> {
-    let mut v = row.to_vec();
-    match (
-        v.replace(config.left_table_idx, Value::Integer(0)).stream(),
-        v.replace(config.right_table_idx, Value::Integer(0))
-            .stream(),
-    ) {
-        (Some(mut l), Some(mut r)) => do_join(&config, l.as_mut(), r.as_mut(), &output, printer),
-        _ => panic!("Wrong row format"),
+    if cfg.join_type == JoinType::Right || cfg.join_type == JoinType::Full {
+        for k in order {
+            let bucket = &right[&k];
+            if !bucket.matched {
+                for r_row in &bucket.rows {
+                    output.send(right_only(r_row, cfg))?;
+                }
+            }
+        }
     }
+    Ok(())
 }
 
-fn get_output_type(input_type: &[ColumnType], cfg: &Config) -> Result<Vec<ColumnType>, CrushError> {
-    let tables: Vec<Option<&Vec<ColumnType>>> = input_type
+/**
+  The output has all the columns of the left side, followed by the columns of the right side
+  except for the keys. Right columns with the same name as a left column get a suffix. Columns
+  of a side that may be missing in outer joins can hold empty cells, so their type is any.
+*/
+fn get_output_type(cfg: &Config) -> Vec<ColumnType> {
+    let left_missing = matches!(cfg.join_type, JoinType::Right | JoinType::Full);
+    let right_missing = matches!(cfg.join_type, JoinType::Left | JoinType::Full);
+    let mut res = cfg
+        .left_types
         .iter()
-        .map(|t| match &t.cell_type {
-            ValueType::TableStream(sub_types) | ValueType::Table(sub_types) => Some(sub_types),
-            _ => None,
+        .enumerate()
+        .map(|(idx, c)| {
+            if left_missing && !cfg.left_keys.contains(&idx) {
+                ColumnType::new(&c.name, ValueType::Any)
+            } else {
+                c.clone()
+            }
         })
-        .collect();
-
-    match (tables[cfg.left_table_idx], tables[cfg.right_table_idx]) {
-        (Some(v1), Some(v2)) => {
-            let mut res = v1.clone();
-            for (idx, c) in v2.iter().enumerate() {
-                if idx != cfg.right_column_idx {
-                    res.push(c.clone());
-                }
+        .collect::<Vec<_>>();
+    if cfg.join_type.has_right_columns() {
+        for (idx, c) in cfg.right_types.iter().enumerate() {
+            if !cfg.right_keys.contains(&idx) {
+                let name = if cfg.left_types.iter().any(|l| l.name == c.name) {
+                    format!("{}{}", c.name, cfg.suffix)
+                } else {
+                    c.name.clone()
+                };
+                let cell_type = if right_missing {
+                    ValueType::Any
+                } else {
+                    c.cell_type.clone()
+                };
+                res.push(ColumnType::new(&name, cell_type));
             }
-            Ok(res)
         }
-        _ => argument_error("Impossible error?"),
     }
+    res
+}
+
+fn join(
+    cfg: Config,
+    l: &mut dyn CrushStream,
+    r: &mut dyn CrushStream,
+    context: ExecutionContext,
+) -> CrushResult<()> {
+    check_keys(&cfg)?;
+    let output = context.output.initialize(get_output_type(&cfg))?;
+    do_join(&cfg, l, r, &output)
 }
 
 pub fn perform(context: ExecutionContext) -> CrushResult<()> {
-    match context.input.recv()? {
-        Value::Struct(s) => {
-            let cfg = parse(&s.local_signature(), context.arguments)?;
-            let output_type = get_output_type(&s.local_signature(), &cfg)?;
-            let output = context.output.initialize(output_type)?;
-            run(cfg, s, output, &context.printer)
+    let options = parse_options(context.arguments.clone())?;
+    match (context.input.recv()?, options.with) {
+        (Value::Struct(s), None) => {
+            let input_type = s.local_signature();
+            let (left_table_idx, right_table_idx, left_keys, right_keys) =
+                find_tables(&input_type, &options.keys)?;
+            let cfg = Config {
+                join_type: options.join_type,
+                left_types: get_sub_type(&input_type[left_table_idx].cell_type)?.to_vec(),
+                right_types: get_sub_type(&input_type[right_table_idx].cell_type)?.to_vec(),
+                left_keys,
+                right_keys,
+                suffix: options.suffix,
+            };
+            let mut v = s.to_vec();
+            match (
+                v.replace(left_table_idx, Value::Integer(0)).stream(),
+                v.replace(right_table_idx, Value::Integer(0)).stream(),
+            ) {
+                (Some(mut l), Some(mut r)) => join(cfg, l.as_mut(), r.as_mut(), context),
+                _ => argument_error("Expected two tables to join"),
+            }
         }
-        _ => argument_error("Expected a struct"),
+        (input, Some(with)) => match (input.stream(), with.stream()) {
+            (Some(mut l), Some(mut r)) => {
+                let mut left_keys = Vec::new();
+                let mut right_keys = Vec::new();
+                for (lf, rf) in &options.keys {
+                    left_keys.push(l.types().find(lf)?);
+                    right_keys.push(r.types().find(rf)?);
+                }
+                let cfg = Config {
+                    join_type: options.join_type,
+                    left_types: l.types().to_vec(),
+                    right_types: r.types().to_vec(),
+                    left_keys,
+                    right_keys,
+                    suffix: options.suffix,
+                };
+                join(cfg, l.as_mut(), r.as_mut(), context)
+            }
+            _ => argument_error("Expected the input and the with argument to be tables"),
+        },
+        _ => argument_error("Expected a struct, or a stream and a with argument"),
     }
 }
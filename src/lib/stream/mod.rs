@@ -1,24 +1,34 @@
 use crate::lang::argument::ArgumentHandler;
+use crate::lang::command::InputType;
 use crate::lang::command::OutputType::{Known, Passthrough, Unknown};
 use crate::lang::errors::CrushResult;
 use crate::lang::scope::Scope;
 use crate::lang::value::ValueType;
 
+mod batch;
 mod head;
 mod reverse;
+mod slice;
 mod sort;
 mod tail;
 mod r#where;
 
+mod columns;
 mod enumerate;
+mod parallel;
 mod select;
 
 mod group;
 mod join;
+mod reshape;
+mod set;
 mod uniq;
+mod window;
 mod zip;
 
+mod aggregate;
 mod count;
+mod reduce;
 mod seq;
 mod sum_avg;
 
@@ -32,8 +42,32 @@ pub fn declare(root: &Scope) -> CrushResult<()> {
             env.declare_command(
                 "tail", tail::perform, true,
                 "tail [lines:integer]", "Return the last lines of the io. Defaults to 10.", None, Passthrough)?;
+            slice::Skip::declare(env)?;
+            slice::Slice::declare(env)?;
+            slice::Sample::declare(env)?;
+            batch::Batch::declare(env)?;
             r#where::Where::declare(env)?;
-            sort::Sort::declare(env)?;
+            env.declare_command(
+                "sort", sort::sort, true,
+                "sort [--reverse] [key:field]... [memory=integer]",
+                "Sort io based on one or more columns",
+                Some(r#"    Rows are sorted on the first key, ties are broken using the second key and so
+    on. A --reverse flag sorts the key that follows it in descending order. Keys
+    can be left out if the io only has one column. The sort is stable.
+
+    Values that can not be compared, like NaN and values of different types, are
+    still sorted consistently: NaN after all other floats, and values of different
+    types by the name of their type.
+
+    When the rows that have been read are estimated to take up more than memory
+    bytes (256 MiB by default), sorted runs of rows are written to a temporary
+    directory and merged at the end, so that inputs larger than memory can be
+    sorted.
+
+    Example:
+
+    ls | sort ^user --reverse ^size"#),
+                Passthrough)?;
             env.declare_command(
                 "reverse", reverse::reverse, true,
                 "reverse", "Reverses the order of the rows in the io", None,
@@ -41,7 +75,29 @@ pub fn declare(root: &Scope) -> CrushResult<()> {
             group::Group::declare(env)?;
             env.declare_command(
                 "join", join::perform, true,
-                "join left:field right:field", "Join two streams together on the specified keys", None,
+                "join (left:field right:field)... [type=string] [with=table] [suffix=string]",
+                "Join two streams together on the specified keys",
+                Some(r#"    The input is either a struct containing the two tables to join, or a stream
+    that is joined with the table given as the with argument. Keys are given as pairs
+    of a left and a right column. With a struct as input, columns can be qualified
+    with the name of their table, like ^l:name.
+
+    The type of join is one of:
+
+    * inner, only rows with a match on both sides (the default)
+    * left, right and full, also rows without a match on the left side, the right
+      side or either side, with empty cells for the missing side
+    * semi, rows of the left side with a match on the right side
+    * anti, rows of the left side without a match on the right side
+
+    The output contains the columns of the left side, followed by the columns of
+    the right side except for the keys. Right columns with the same name as a left
+    column get the suffix "_right", or the one specified as the suffix argument.
+
+    Examples:
+
+    data l=home r=age | join ^l:name ^r:name type="left"
+    ps | join ^user ^owner with=inventory type="anti""#),
                 Unknown)?;
             env.declare_command(
                 "uniq", uniq::uniq, true,
@@ -73,16 +129,51 @@ pub fn declare(root: &Scope) -> CrushResult<()> {
                 "avg column:field",
                 "Calculate the average of the specific column across all rows",
                 example!("ps | avg ^cpu"), Unknown)?;
+            aggregate::Median::declare(env)?;
+            aggregate::Percentile::declare(env)?;
+            aggregate::Stddev::declare(env)?;
+            aggregate::First::declare(env)?;
+            aggregate::Last::declare(env)?;
+            aggregate::Collect::declare(env)?;
+            aggregate::DistinctCount::declare(env)?;
+            reduce::Reduce::declare(env)?;
             env.declare_command(
                 "select", select::select, true,
-                "select copy_fields:field... [%] new_field=definition:command",
+                "select copy_fields:field... [%] new_field=definition:command [parallel=integer]",
                 "Pass on some old fields and calculate new ones for each line of io",
-                example!(r#"ls | select ^user path={"{}/{}":format (pwd) file}"#), Unknown)?;
+                Some(concat!(
+                    "    With parallel set, the new fields are calculated for several rows at a time on separate\n",
+                    "    threads. Rows are still output in input order. In either mode, the first row, in input\n",
+                    "    order, that a calculation fails on stops select, and its error is reported once.\n\n",
+                    "    Example:\n\n    ",
+                    r#"ls | select ^user path={"{}/{}":format (pwd) file}"#)), Unknown)?;
             env.declare_command(
                 "enumerate", enumerate::perform, true,
                 "enumerate", "Prepend a column containing the row number to each row of the io", None, Unknown)?;
+            columns::Drop::declare(env)?;
+            columns::Rename::declare(env)?;
+            columns::Reorder::declare(env)?;
+            set::Distinct::declare(env)?;
+            set::Union::declare(env)?;
+            set::Intersect::declare(env)?;
+            set::Except::declare(env)?;
+            reshape::Explode::declare(env)?;
+            reshape::Flatten::declare(env)?;
+            reshape::Pivot::declare(env)?;
+            reshape::Unpivot::declare(env)?;
+            window::Window::declare(env)?;
             zip::Zip::declare(env)?;
             seq::Seq::declare(env)?;
+
+            let numeric = vec![ValueType::Integer, ValueType::Float, ValueType::Duration];
+            let ordered = vec![ValueType::Integer, ValueType::Float, ValueType::Duration, ValueType::Time];
+            env.declare_input("sort", InputType::Table)?;
+            env.declare_input("uniq", InputType::Table)?;
+            env.declare_input("select", InputType::Table)?;
+            env.declare_input("sum", InputType::Column(numeric.clone()))?;
+            env.declare_input("avg", InputType::Column(numeric))?;
+            env.declare_input("min", InputType::Column(ordered.clone()))?;
+            env.declare_input("max", InputType::Column(ordered))?;
             Ok(())
         }))?;
     root.r#use(&e);
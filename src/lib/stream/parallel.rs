@@ -0,0 +1,98 @@
+use crate::lang::errors::{to_crush_error, CrushResult};
+use crate::lang::printer::Printer;
+use crate::lang::stream::Stream;
+use crate::lang::table::Row;
+use crate::util::thread::build;
+use crossbeam::unbounded;
+use std::collections::HashMap;
+
+/**
+  Apply a function to every row of a stream on a pool of worker threads, one per function in
+  workers, and pass each row and its result on to emit in input order.
+
+  The input is read on the calling thread, and at most twice as many rows as there are workers
+  are in flight at a time, so a slow row doesn't make the rows after it pile up in memory. The
+  error of the first row, in input order, that a worker fails on stops the whole operation and
+  is returned. Workers should report errors through captured, so that rows that are in flight
+  when this happens don't print errors of their own.
+*/
+pub fn map<R, F>(
+    name: &str,
+    mut input: Stream,
+    workers: Vec<F>,
+    mut emit: impl FnMut(Row, R) -> CrushResult<()>,
+) -> CrushResult<()>
+where
+    R: Send + 'static,
+    F: FnMut(&Row) -> CrushResult<R> + Send + 'static,
+{
+    let window = workers.len() * 2;
+    let (job_sender, job_receiver) = unbounded::<(usize, Row)>();
+    let (result_sender, result_receiver) = unbounded::<(usize, Row, CrushResult<R>)>();
+    for mut worker in workers {
+        let jobs = job_receiver.clone();
+        let results = result_sender.clone();
+        to_crush_error(build(name).spawn(move || {
+            while let Ok((idx, row)) = jobs.recv() {
+                let result = worker(&row);
+                if results.send((idx, row, result)).is_err() {
+                    break;
+                }
+            }
+        }))?;
+    }
+    drop(job_receiver);
+    drop(result_sender);
+
+    let mut job_sender = Some(job_sender);
+    let mut done = HashMap::new();
+    let mut next_in = 0;
+    let mut next_out = 0;
+    loop {
+        while next_in - next_out < window {
+            match job_sender.as_ref() {
+                Some(sender) => match input.read() {
+                    Ok(row) => {
+                        to_crush_error(sender.send((next_in, row)))?;
+                        next_in += 1;
+                    }
+                    Err(_) => job_sender = None,
+                },
+                None => break,
+            }
+        }
+        if next_out == next_in {
+            return Ok(());
+        }
+        let (idx, row, result) = to_crush_error(result_receiver.recv())?;
+        done.insert(idx, (row, result));
+        while let Some((row, result)) = done.remove(&next_out) {
+            emit(row, result?)?;
+            next_out += 1;
+        }
+    }
+}
+
+/**
+  Call f with a printer that captures errors instead of printing them, and return the first error
+  reported through it, or else the result of f. This waits for every command given the printer
+  to finish with it.
+*/
+pub fn captured<R>(
+    printer: &Printer,
+    f: impl FnOnce(&Printer) -> CrushResult<R>,
+) -> CrushResult<R> {
+    let (printer, errors) = printer.capturing();
+    let result = f(&printer);
+    drop(printer);
+    let mut first = None;
+    for err in errors.iter() {
+        if first.is_none() {
+            first = Some(err);
+        }
+    }
+    match first {
+        Some(err) => Err(err),
+        None => result,
+    }
+}
@@ -0,0 +1,60 @@
+use crate::lang::argument::{Argument, ArgumentHandler};
+use crate::lang::command::Command;
+use crate::lang::errors::{error, CrushResult};
+use crate::lang::execution_context::ExecutionContext;
+use crate::lang::stream::{black_hole, channels, empty_channel};
+use crate::lang::value::Value;
+use signature::signature;
+
+#[signature(
+    reduce,
+    can_block = true,
+    short = "Combine all rows of the input into a single value",
+    long = "The reducer is called once per row with two arguments, the value so far and the row, and returns the next value. The first call gets the initial value. Rows with a single column are passed as the value of that column, other rows are passed as a struct.",
+    long = "The output is the value returned by the last call, or the initial value if the input is empty.",
+    example = "seq 5 | reduce 0 {|sum row| sum + row}"
+)]
+pub struct Reduce {
+    #[description("the value passed to the first call of the reducer.")]
+    initial: Value,
+    #[description("a closure taking the value so far and a row, and returning the next value.")]
+    reducer: Command,
+}
+
+pub fn reduce(context: ExecutionContext) -> CrushResult<()> {
+    let cfg: Reduce = Reduce::parse(context.arguments, &context.printer)?;
+    match context.input.recv()?.stream() {
+        Some(mut input) => {
+            let types = input.types().to_vec();
+            let base_context = ExecutionContext {
+                input: empty_channel(),
+                output: black_hole(),
+                arguments: vec![],
+                env: context.env.clone(),
+                this: None,
+                printer: context.printer.clone(),
+            };
+            let mut res = cfg.initial;
+            while let Ok(row) = input.read() {
+                let element = if types.len() == 1 {
+                    row.into_vec().remove(0)
+                } else {
+                    Value::Struct(row.into_struct(&types))
+                };
+                let (sender, receiver) = channels();
+                cfg.reducer.invoke(
+                    base_context
+                        .clone()
+                        .with_args(
+                            vec![Argument::unnamed(res), Argument::unnamed(element)],
+                            None,
+                        )
+                        .with_sender(sender),
+                )?;
+                res = receiver.recv()?;
+            }
+            context.output.send(res)
+        }
+        None => error("Expected a stream"),
+    }
+}
@@ -0,0 +1,500 @@
+use crate::lang::argument::ArgumentHandler;
+use crate::lang::command::InputType;
+use crate::lang::errors::{argument_error, data_error, error, CrushResult};
+use crate::lang::execution_context::ExecutionContext;
+use crate::lang::stream::{OutputStream, Stream, ValueReceiver};
+use crate::lang::table::{ColumnType, ColumnVec, Row};
+use crate::lang::value::{Field, Value, ValueType};
+use signature::signature;
+use std::collections::HashMap;
+
+fn input_stream(input: &ValueReceiver) -> CrushResult<Stream> {
+    match input.recv()?.stream() {
+        Some(input) => Ok(input),
+        None => error("Expected a stream"),
+    }
+}
+
+/** Columns of type any may hold values that can't be hashed, so every cell of the key is checked. */
+fn hashable(cell: &Value) -> CrushResult<Value> {
+    if !cell.value_type().is_hashable() {
+        return data_error(
+            format!(
+                "Can't merge rows holding values of type {}",
+                cell.value_type().to_string()
+            )
+            .as_str(),
+        );
+    }
+    Ok(cell.clone())
+}
+
+/** The type shared by all cells of a new column, or any if they differ or some are missing. */
+fn common_type<'a>(mut cells: impl Iterator<Item = &'a Option<Value>>) -> ValueType {
+    let first = match cells.next() {
+        Some(Some(value)) => value.value_type(),
+        _ => return ValueType::Any,
+    };
+    for cell in cells {
+        match cell {
+            Some(value) if value.value_type() == first => {}
+            _ => return ValueType::Any,
+        }
+    }
+    first
+}
+
+fn check_unique(types: &[ColumnType]) -> CrushResult<()> {
+    for (idx, column) in types.iter().enumerate() {
+        if types[..idx].iter().any(|c| c.name == column.name) {
+            return argument_error(
+                format!("Multiple columns would be named {}", column.name).as_str(),
+            );
+        }
+    }
+    Ok(())
+}
+
+/** A column name made from a value, e.g. a struct field name or a dict key. */
+fn name_of(value: &Value) -> String {
+    match value {
+        Value::String(s) => s.to_string(),
+        v => v.to_string(),
+    }
+}
+
+#[signature(
+    explode,
+    can_block = true,
+    input = InputType::Table,
+    short = "Output one row for every element of a list or nested table in the specified column",
+    long = "The other columns of the row are repeated for each element. Rows where the column is empty, or holds an empty list or table, are skipped.",
+    long = "The rows of a nested table are expanded into one column per table column, named after the exploded column and the table column, e.g. files_name. If the type of the nested tables isn't known, each row is output as a struct instead.",
+    example = "json:from users.json | explode ^groups"
+)]
+pub struct Explode {
+    #[description("the column to explode.")]
+    column: Field,
+}
+
+enum Elements {
+    Values,
+    Rows(usize),
+}
+
+fn explode_cell(
+    cell: Value,
+    elements: &Elements,
+    nested_type: &Option<Vec<ColumnType>>,
+) -> CrushResult<Vec<Vec<Value>>> {
+    match (cell, elements) {
+        (Value::Empty(), _) => Ok(vec![]),
+        (Value::List(l), Elements::Values) => Ok(l.dump().into_iter().map(|v| vec![v]).collect()),
+        (cell, _) => match cell.stream() {
+            Some(mut nested) => {
+                let types = nested.types().to_vec();
+                let mut res = Vec::new();
+                while let Ok(row) = nested.read() {
+                    res.push(match (elements, nested_type) {
+                        (Elements::Rows(len), Some(expected)) => {
+                            if &types != expected || row.len() != *len {
+                                return data_error("Nested table has an unexpected type");
+                            }
+                            row.into_vec()
+                        }
+                        _ => vec![Value::Struct(row.into_struct(&types))],
+                    });
+                }
+                Ok(res)
+            }
+            None => data_error(
+                format!(
+                    "Can't explode a value of type {}",
+                    cell.value_type().to_string()
+                )
+                .as_str(),
+            ),
+        },
+    }
+}
+
+pub fn explode(context: ExecutionContext) -> CrushResult<()> {
+    let cfg: Explode = Explode::parse(context.arguments, &context.printer)?;
+    let mut input = input_stream(&context.input)?;
+    let types = input.types().to_vec();
+    let idx = types.as_slice().find(&cfg.column)?;
+    let name = types[idx].name.clone();
+
+    let (elements, nested_type, exploded_type) = match &types[idx].cell_type {
+        ValueType::List(element_type) => (
+            Elements::Values,
+            None,
+            vec![ColumnType::new(&name, element_type.as_ref().clone())],
+        ),
+        ValueType::Table(nested) | ValueType::TableStream(nested) => (
+            Elements::Rows(nested.len()),
+            Some(nested.clone()),
+            nested
+                .iter()
+                .map(|c| ColumnType::new(&format!("{}_{}", name, c.name), c.cell_type.clone()))
+                .collect(),
+        ),
+        ValueType::Any => (
+            Elements::Values,
+            None,
+            vec![ColumnType::new(&name, ValueType::Any)],
+        ),
+        t => {
+            return argument_error(
+                format!("Can't explode a column of type {}", t.to_string()).as_str(),
+            )
+        }
+    };
+
+    let mut output_type = types.clone();
+    output_type.splice(idx..idx + 1, exploded_type);
+    check_unique(&output_type)?;
+    let output = context.output.initialize(output_type)?;
+
+    while let Ok(row) = input.read() {
+        let mut cells = row.into_vec();
+        let cell = std::mem::replace(&mut cells[idx], Value::Empty());
+        for element in explode_cell(cell, &elements, &nested_type)? {
+            let mut res = cells.clone();
+            res.splice(idx..idx + 1, element);
+            output.send(Row::new(res))?;
+        }
+    }
+    Ok(())
+}
+
+#[signature(
+    flatten,
+    can_block = true,
+    input = InputType::Table,
+    short = "Expand struct and dict cells into one column per field",
+    long = "The new columns are named after the flattened column and the field or key, e.g. owner_name, and replace the flattened column. Fields that are missing in some rows are empty in those rows.",
+    long = "Because the set of fields is only known once every row has been seen, the whole input is read before any output is produced.",
+    example = "json:from users.json | flatten ^address"
+)]
+pub struct Flatten {
+    #[unnamed()]
+    #[description("the columns to flatten. By default, every column that only holds structs and dicts is flattened.")]
+    columns: Vec<Field>,
+}
+
+fn fields_of(value: &Value) -> Option<Vec<(String, Value)>> {
+    match value {
+        Value::Struct(s) => Some(s.local_elements()),
+        Value::Dict(d) => Some(
+            d.elements()
+                .into_iter()
+                .map(|(k, v)| (name_of(&k), v))
+                .collect(),
+        ),
+        _ => None,
+    }
+}
+
+/** The fields of a flattened column, and the value of each field in each row. */
+struct Expanded {
+    names: Vec<String>,
+    cells: Vec<Vec<Option<Value>>>,
+}
+
+pub fn flatten(context: ExecutionContext) -> CrushResult<()> {
+    let cfg: Flatten = Flatten::parse(context.arguments, &context.printer)?;
+    let mut input = input_stream(&context.input)?;
+    let types = input.types().to_vec();
+    let mut rows = Vec::new();
+    while let Ok(row) = input.read() {
+        rows.push(row.into_vec());
+    }
+
+    let flattenable = |idx: usize| {
+        rows.iter().all(|row: &Vec<Value>| match &row[idx] {
+            Value::Empty() => true,
+            v => fields_of(v).is_some(),
+        })
+    };
+    let targets = if cfg.columns.is_empty() {
+        (0..types.len())
+            .filter(|idx| {
+                matches!(
+                    types[*idx].cell_type,
+                    ValueType::Struct | ValueType::Dict(_, _) | ValueType::Any
+                ) && flattenable(*idx)
+                    && rows.iter().any(|row| fields_of(&row[*idx]).is_some())
+            })
+            .collect::<Vec<_>>()
+    } else {
+        let mut targets = Vec::new();
+        for field in &cfg.columns {
+            let idx = types.as_slice().find(field)?;
+            if !flattenable(idx) {
+                return argument_error(
+                    format!(
+                        "Column {} holds values other than structs and dicts",
+                        types[idx].name
+                    )
+                    .as_str(),
+                );
+            }
+            targets.push(idx);
+        }
+        targets
+    };
+
+    let mut expanded: HashMap<usize, Expanded> = HashMap::new();
+    for idx in &targets {
+        let mut names: Vec<String> = Vec::new();
+        let mut cells = Vec::with_capacity(rows.len());
+        for row in &rows {
+            let mut row_cells = vec![None; names.len()];
+            for (name, value) in fields_of(&row[*idx]).unwrap_or_default() {
+                match names.iter().position(|n| n == &name) {
+                    Some(pos) => row_cells[pos] = Some(value),
+                    None => {
+                        names.push(name);
+                        row_cells.push(Some(value));
+                    }
+                }
+            }
+            cells.push(row_cells);
+        }
+        for row_cells in cells.iter_mut() {
+            row_cells.resize(names.len(), None);
+        }
+        expanded.insert(*idx, Expanded { names, cells });
+    }
+
+    let mut output_type = Vec::new();
+    for (idx, column) in types.iter().enumerate() {
+        match expanded.get(&idx) {
+            Some(Expanded { names, cells }) => {
+                for (pos, name) in names.iter().enumerate() {
+                    output_type.push(ColumnType::new(
+                        &format!("{}_{}", column.name, name),
+                        common_type(cells.iter().map(|c| &c[pos])),
+                    ));
+                }
+            }
+            None => output_type.push(column.clone()),
+        }
+    }
+    check_unique(&output_type)?;
+    let output = context.output.initialize(output_type)?;
+
+    for (row_idx, row) in rows.into_iter().enumerate() {
+        let mut res = Vec::new();
+        for (idx, cell) in row.into_iter().enumerate() {
+            match expanded.get_mut(&idx) {
+                Some(Expanded { cells, .. }) => res.extend(
+                    std::mem::take(&mut cells[row_idx])
+                        .into_iter()
+                        .map(|c| c.unwrap_or_else(Value::Empty)),
+                ),
+                None => res.push(cell),
+            }
+        }
+        output.send(Row::new(res))?;
+    }
+    Ok(())
+}
+
+#[signature(
+    pivot,
+    can_block = true,
+    input = InputType::Table,
+    short = "Turn the distinct values of a column into columns",
+    long = "The values of the names column become the names of new columns, and the cells of these columns are taken from the values column. Rows that are equal in all other columns are merged into a single row. Cells with no matching row are empty. This is the inverse of unpivot.",
+    long = "Because the set of columns is only known once every row has been seen, the whole input is read before any output is produced.",
+    example = "metrics | pivot names=^metric values=^reading"
+)]
+pub struct Pivot {
+    #[description("the column holding the names of the new columns.")]
+    names: Field,
+    #[description("the column holding the cells of the new columns.")]
+    values: Field,
+}
+
+pub fn pivot(context: ExecutionContext) -> CrushResult<()> {
+    let cfg: Pivot = Pivot::parse(context.arguments, &context.printer)?;
+    let mut input = input_stream(&context.input)?;
+    let types = input.types().to_vec();
+    let names_idx = types.as_slice().find(&cfg.names)?;
+    let values_idx = types.as_slice().find(&cfg.values)?;
+    if names_idx == values_idx {
+        return argument_error("The names and values columns must be different");
+    }
+    let key_indices = (0..types.len())
+        .filter(|idx| *idx != names_idx && *idx != values_idx)
+        .collect::<Vec<_>>();
+    for idx in &key_indices {
+        if !types[*idx].cell_type.is_hashable() {
+            return argument_error(
+                format!(
+                    "Can't merge rows on column {} of type {}",
+                    types[*idx].name,
+                    types[*idx].cell_type.to_string()
+                )
+                .as_str(),
+            );
+        }
+    }
+
+    let mut names: Vec<String> = Vec::new();
+    let mut keys: Vec<Vec<Value>> = Vec::new();
+    let mut cells: Vec<Vec<Option<Value>>> = Vec::new();
+    let mut key_lookup: HashMap<Vec<Value>, usize> = HashMap::new();
+    while let Ok(row) = input.read() {
+        let mut row = row.into_vec();
+        let key = key_indices
+            .iter()
+            .map(|idx| hashable(&row[*idx]))
+            .collect::<CrushResult<Vec<_>>>()?;
+        let name = name_of(&row[names_idx]);
+        let value = std::mem::replace(&mut row[values_idx], Value::Empty());
+
+        let row_idx = *key_lookup.entry(key.clone()).or_insert_with(|| {
+            keys.push(key);
+            cells.push(vec![None; names.len()]);
+            keys.len() - 1
+        });
+        let column = match names.iter().position(|n| n == &name) {
+            Some(column) => column,
+            None => {
+                names.push(name.clone());
+                names.len() - 1
+            }
+        };
+        let row_cells = &mut cells[row_idx];
+        row_cells.resize(names.len(), None);
+        if row_cells[column].is_some() {
+            return data_error(
+                format!(
+                    "Multiple values for column {} in the same row, group the input first",
+                    name
+                )
+                .as_str(),
+            );
+        }
+        row_cells[column] = Some(value);
+    }
+    for row_cells in cells.iter_mut() {
+        row_cells.resize(names.len(), None);
+    }
+
+    let mut output_type = key_indices
+        .iter()
+        .map(|idx| types[*idx].clone())
+        .collect::<Vec<_>>();
+    for (pos, name) in names.iter().enumerate() {
+        output_type.push(ColumnType::new(
+            name,
+            common_type(cells.iter().map(|c| &c[pos])),
+        ));
+    }
+    check_unique(&output_type)?;
+    let output = context.output.initialize(output_type)?;
+    for (mut key, row_cells) in keys.into_iter().zip(cells) {
+        key.extend(
+            row_cells
+                .into_iter()
+                .map(|c| c.unwrap_or_else(Value::Empty)),
+        );
+        output.send(Row::new(key))?;
+    }
+    Ok(())
+}
+
+#[signature(
+    unpivot,
+    can_block = true,
+    input = InputType::Table,
+    short = "Turn columns into rows",
+    long = "Every input row is output once for each of the specified columns, with the other columns repeated, the name of the column in the names column, and its cell in the values column. Empty cells are skipped. This is the inverse of pivot.",
+    example = "ps | unpivot ^cpu ^rss names=\"metric\" values=\"reading\""
+)]
+pub struct Unpivot {
+    #[unnamed()]
+    #[description("the columns to turn into rows.")]
+    columns: Vec<Field>,
+    #[description("the name of the column to hold the column names.")]
+    #[default("name")]
+    names: String,
+    #[description("the name of the column to hold the cells.")]
+    #[default("value")]
+    values: String,
+}
+
+fn send_unpivoted(
+    row: Row,
+    indices: &[usize],
+    types: &[ColumnType],
+    output: &OutputStream,
+) -> CrushResult<()> {
+    let mut cells = row.into_vec();
+    let unpivoted = indices
+        .iter()
+        .map(|idx| std::mem::replace(&mut cells[*idx], Value::Empty()))
+        .collect::<Vec<_>>();
+    let kept = cells
+        .into_iter()
+        .enumerate()
+        .filter(|(idx, _)| !indices.contains(idx))
+        .map(|(_, cell)| cell)
+        .collect::<Vec<_>>();
+    for (idx, cell) in indices.iter().zip(unpivoted) {
+        if let Value::Empty() = cell {
+            continue;
+        }
+        let mut res = kept.clone();
+        res.push(Value::string(&types[*idx].name));
+        res.push(cell);
+        output.send(Row::new(res))?;
+    }
+    Ok(())
+}
+
+pub fn unpivot(context: ExecutionContext) -> CrushResult<()> {
+    let cfg: Unpivot = Unpivot::parse(context.arguments, &context.printer)?;
+    let mut input = input_stream(&context.input)?;
+    let types = input.types().to_vec();
+    if cfg.columns.is_empty() {
+        return argument_error("No columns to unpivot specified");
+    }
+    let mut indices = Vec::new();
+    for field in &cfg.columns {
+        let idx = types.as_slice().find(field)?;
+        if indices.contains(&idx) {
+            return argument_error(
+                format!("Column {} specified more than once", types[idx].name).as_str(),
+            );
+        }
+        indices.push(idx);
+    }
+
+    let first = types[indices[0]].cell_type.clone();
+    let value_type = if indices.iter().all(|idx| types[*idx].cell_type == first) {
+        first
+    } else {
+        ValueType::Any
+    };
+
+    let mut output_type = types
+        .iter()
+        .enumerate()
+        .filter(|(idx, _)| !indices.contains(idx))
+        .map(|(_, c)| c.clone())
+        .collect::<Vec<_>>();
+    output_type.push(ColumnType::new(&cfg.names, ValueType::String));
+    output_type.push(ColumnType::new(&cfg.values, value_type));
+    check_unique(&output_type)?;
+    let output = context.output.initialize(output_type)?;
+
+    while let Ok(row) = input.read() {
+        send_unpivoted(row, &indices, &types, &output)?;
+    }
+    Ok(())
+}
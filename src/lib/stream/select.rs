@@ -1,8 +1,12 @@
 use crate::lang::command::Command;
-use crate::lang::errors::error;
+use crate::lang::errors::{error, to_crush_error};
 use crate::lang::execution_context::ExecutionContext;
 use crate::lang::stream::{channels, empty_channel, Stream};
+use crate::lang::printer::Printer;
+use crate::lang::scope::Scope;
 use crate::lang::table::ColumnVec;
+use crate::lib::stream::parallel;
+use std::convert::TryFrom;
 use crate::{
     lang::errors::argument_error,
     lang::errors::CrushResult,
@@ -11,6 +15,7 @@ use crate::{
     util::replace::Replace,
 };
 
+#[derive(Clone)]
 enum Location {
     Replace(usize),
     Append(String),
@@ -21,9 +26,75 @@ enum Source {
     Argument(usize),
 }
 
+impl Source {
+    fn copy(&self) -> Source {
+        match self {
+            Source::Closure(closure) => Source::Closure(closure.copy()),
+            Source::Argument(idx) => Source::Argument(*idx),
+        }
+    }
+}
+
 pub struct Config {
     copy: bool,
     columns: Vec<(Location, Source)>,
+    parallel: usize,
+}
+
+impl Config {
+    fn copy(&self) -> Config {
+        Config {
+            copy: self.copy,
+            columns: self
+                .columns
+                .iter()
+                .map(|(location, source)| (location.clone(), source.copy()))
+                .collect(),
+            parallel: self.parallel,
+        }
+    }
+}
+
+fn evaluate(
+    config: &Config,
+    row: &Row,
+    input_type: &[ColumnType],
+    env: &Scope,
+    printer: &Printer,
+) -> CrushResult<Vec<Value>> {
+    let mut result = if config.copy {
+        row.cells().clone()
+    } else {
+        Vec::new()
+    };
+    for (location, source) in &config.columns {
+        let value = match source {
+            Source::Closure(closure) => {
+                let arguments: Vec<Argument> = row
+                    .cells()
+                    .iter()
+                    .zip(input_type)
+                    .map(|(cell, cell_type)| Argument::named(&cell_type.name, cell.clone()))
+                    .collect();
+                let (sender, receiver) = channels();
+                closure.invoke(ExecutionContext {
+                    input: empty_channel(),
+                    output: sender,
+                    arguments,
+                    env: env.clone(),
+                    this: None,
+                    printer: printer.clone(),
+                })?;
+                receiver.recv()?
+            }
+            Source::Argument(idx) => row.cells()[*idx].clone(),
+        };
+        match location {
+            Location::Append(_) => result.push(value),
+            Location::Replace(idx) => result[*idx] = value,
+        }
+    }
+    Ok(result)
 }
 
 pub fn run(config: Config, mut input: Stream, context: ExecutionContext) -> CrushResult<()> {
@@ -34,97 +105,58 @@ pub fn run(config: Config, mut input: Stream, context: ExecutionContext) -> Crus
         Vec::new()
     };
 
-    let mut first_result = Vec::new();
-
-    match input.read() {
-        Ok(row) => {
-            if config.copy {
-                first_result.append(&mut row.cells().clone());
+    let first_result = match input.read() {
+        Ok(row) => evaluate(&config, &row, &input_type, &context.env, &context.printer)?,
+        Err(_) => return Ok(()),
+    };
+    for (location, _) in &config.columns {
+        match location {
+            Location::Append(name) => {
+                let value_type = first_result[output_type.len()].value_type();
+                output_type.push(ColumnType::new(name.as_ref(), value_type));
             }
-            for (location, source) in &config.columns {
-                let value = match source {
-                    Source::Closure(closure) => {
-                        let (sender, receiver) = channels();
-                        let arguments: Vec<Argument> = row
-                            .cells()
-                            .iter()
-                            .zip(&input_type)
-                            .map(|(cell, cell_type)| {
-                                Argument::named(cell_type.name.as_ref(), cell.clone())
-                            })
-                            .collect();
-                        closure.invoke(ExecutionContext {
-                            input: empty_channel(),
-                            output: sender,
-                            arguments,
-                            env: context.env.clone(),
-                            this: None,
-                            printer: context.printer.clone(),
-                        })?;
-                        receiver.recv()?
-                    }
-                    Source::Argument(idx) => row.cells()[*idx].clone(),
-                };
-
-                match location {
-                    Location::Append(name) => {
-                        output_type.push(ColumnType::new(name.as_ref(), value.value_type()));
-                        first_result.push(value);
-                    }
-                    Location::Replace(idx) => {
-                        output_type.replace(
-                            *idx,
-                            ColumnType::new(output_type[*idx].name.as_ref(), value.value_type()),
-                        );
-                        first_result[*idx] = value;
-                    }
-                }
+            Location::Replace(idx) => {
+                output_type.replace(
+                    *idx,
+                    ColumnType::new(
+                        output_type[*idx].name.as_ref(),
+                        first_result[*idx].value_type(),
+                    ),
+                );
             }
         }
-        Err(_) => return Ok(()),
     }
 
     let output = context.output.initialize(output_type)?;
     output.send(Row::new(first_result))?;
 
-    while let Ok(row) = input.read() {
-        let mut next_result = Vec::new();
-
-        if config.copy {
-            next_result.append(&mut row.cells().clone());
-        }
-        for (location, source) in &config.columns {
-            let value = match source {
-                Source::Closure(closure) => {
-                    let arguments: Vec<Argument> = row
-                        .cells()
-                        .iter()
-                        .zip(&input_type)
-                        .map(|(cell, cell_type)| Argument::named(&cell_type.name, cell.clone()))
-                        .collect();
-                    let (sender, receiver) = channels();
-                    closure.invoke(ExecutionContext {
-                        input: empty_channel(),
-                        output: sender,
-                        arguments,
-                        env: context.env.clone(),
-                        this: None,
-                        printer: context.printer.clone(),
-                    })?;
-                    receiver.recv()?
+    if config.parallel > 1 {
+        let workers = (0..config.parallel)
+            .map(|_| {
+                let config = config.copy();
+                let input_type = input_type.clone();
+                let env = context.env.clone();
+                let printer = context.printer.clone();
+                move |row: &Row| {
+                    parallel::captured(&printer, |printer| {
+                        evaluate(&config, row, &input_type, &env, printer)
+                    })
                 }
-                Source::Argument(idx) => row.cells()[*idx].clone(),
-            };
-            match location {
-                Location::Append(_) => {
-                    next_result.push(value);
-                }
-                Location::Replace(idx) => {
-                    next_result[*idx] = value;
-                }
-            }
-        }
-        output.send(Row::new(next_result))?;
+            })
+            .collect();
+        return parallel::map("select-worker", input, workers, |_, result| {
+            output.send(Row::new(result))
+        });
+    }
+
+    while let Ok(row) = input.read() {
+        output.send(Row::new(evaluate(
+            &config,
+            &row,
+            &input_type,
+            &context.env,
+            &context.printer,
+        )?))?;
     }
     Ok(())
 }
@@ -134,6 +166,7 @@ pub fn select(mut context: ExecutionContext) -> CrushResult<()> {
         Some(input) => {
             let mut copy = false;
             let mut columns = Vec::new();
+            let mut parallel = 1;
 
             if context.arguments.len() == 0 {
                 return argument_error("No columns selected");
@@ -151,6 +184,12 @@ pub fn select(mut context: ExecutionContext) -> CrushResult<()> {
             let input_type = input.types();
             for a in &context.arguments {
                 match (a.argument_type.as_deref(), a.value.clone()) {
+                    (Some("parallel"), Value::Integer(i)) => {
+                        parallel = to_crush_error(usize::try_from(i))?;
+                        if parallel == 0 {
+                            return argument_error("parallel must be at least 1");
+                        }
+                    }
                     (Some(name), Value::Command(closure)) => {
                         match (copy, input_type.find_str(name)) {
                             (true, Ok(idx)) => {
@@ -180,7 +219,15 @@ pub fn select(mut context: ExecutionContext) -> CrushResult<()> {
                 }
             }
 
-            run(Config { columns, copy }, input, context)
+            run(
+                Config {
+                    columns,
+                    copy,
+                    parallel,
+                },
+                input,
+                context,
+            )
         }
         _ => error("Expected a stream"),
     }
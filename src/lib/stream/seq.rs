@@ -1,11 +1,16 @@
 use crate::lang::argument::ArgumentHandler;
+use crate::lang::command::OutputType::Known;
 use crate::lang::errors::CrushResult;
 use crate::lang::execution_context::ExecutionContext;
 use crate::lang::table::ColumnType;
 use crate::lang::{table::Row, value::Value, value::ValueType};
 use signature::signature;
 
-#[signature(seq, short = "Return a stream of sequential numbers")]
+#[signature(
+    seq,
+    output = Known(ValueType::TableStream(vec![ColumnType::new("value", ValueType::Integer)])),
+    short = "Return a stream of sequential numbers"
+)]
 #[derive(Debug)]
 pub struct Seq {
     #[default(i128::max_value())]
@@ -0,0 +1,276 @@
+use crate::lang::argument::ArgumentHandler;
+use crate::lang::command::InputType;
+use crate::lang::command::OutputType::Passthrough;
+use crate::lang::errors::{argument_error, data_error, error, CrushResult};
+use crate::lang::execution_context::ExecutionContext;
+use crate::lang::stream::{Stream, ValueReceiver};
+use crate::lang::table::{ColumnType, ColumnVec, Row};
+use crate::lang::value::{Field, Value, ValueType};
+use signature::signature;
+use std::collections::HashSet;
+
+fn input_stream(input: &ValueReceiver) -> CrushResult<Stream> {
+    match input.recv()?.stream() {
+        Some(input) => Ok(input),
+        None => error("Expected a stream"),
+    }
+}
+
+/** Columns of type any may hold values that can't be hashed, so every cell is checked. */
+fn hashable(row: Row) -> CrushResult<Row> {
+    for cell in row.cells() {
+        if !cell.value_type().is_hashable() {
+            return data_error(
+                format!(
+                    "Can't compare rows holding values of type {}",
+                    cell.value_type().to_string()
+                )
+                .as_str(),
+            );
+        }
+    }
+    Ok(row)
+}
+
+fn signature_string(types: &[ColumnType]) -> String {
+    types
+        .iter()
+        .map(|t| t.to_string())
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+/**
+  Check that the other streams have the same columns as the input, and return the type of the
+  output. Columns of type any are compatible with any other type, and are of type any in the
+  output.
+*/
+fn combined_type(input: &[ColumnType], others: &[Stream]) -> CrushResult<Vec<ColumnType>> {
+    let mut res = input.to_vec();
+    for t in &res {
+        if !t.cell_type.is_hashable() {
+            return argument_error(
+                format!(
+                    "Can't compare rows with a column of type {}",
+                    t.cell_type.to_string()
+                )
+                .as_str(),
+            );
+        }
+    }
+    for other in others {
+        let other_type = other.types();
+        let incompatible = |reason: String| {
+            argument_error(
+                format!(
+                    "Incompatible signatures, {}: ({}) vs ({})",
+                    reason,
+                    signature_string(input),
+                    signature_string(other_type)
+                )
+                .as_str(),
+            )
+        };
+        if other_type.len() != input.len() {
+            return incompatible(format!(
+                "expected {} columns, got {}",
+                input.len(),
+                other_type.len()
+            ));
+        }
+        for (idx, (a, b)) in input.iter().zip(other_type).enumerate() {
+            if a.name != b.name {
+                return incompatible(format!("column {} is named {}", a.name, b.name));
+            }
+            match (&a.cell_type, &b.cell_type) {
+                (x, y) if x == y => {}
+                (ValueType::Any, _) | (_, ValueType::Any) => {
+                    res[idx] = ColumnType::new(&a.name, ValueType::Any)
+                }
+                (x, y) => {
+                    return incompatible(format!(
+                        "column {} is of type {}, not {}",
+                        a.name,
+                        y.to_string(),
+                        x.to_string()
+                    ))
+                }
+            }
+        }
+    }
+    Ok(res)
+}
+
+fn other_streams(others: Vec<Value>) -> CrushResult<Vec<Stream>> {
+    others
+        .into_iter()
+        .map(|v| match v.stream() {
+            Some(s) => Ok(s),
+            None => argument_error(
+                format!(
+                    "Expected a table or a stream, got a value of type {}",
+                    v.value_type().to_string()
+                )
+                .as_str(),
+            ),
+        })
+        .collect()
+}
+
+fn read_set(mut stream: Stream) -> CrushResult<HashSet<Row>> {
+    let mut res = HashSet::new();
+    while let Ok(row) = stream.read() {
+        res.insert(hashable(row)?);
+    }
+    Ok(res)
+}
+
+#[signature(
+    distinct,
+    can_block = true,
+    output = Passthrough,
+    input = InputType::Table,
+    short = "Only pass on the first of all rows that are equal",
+    long = "By default, rows are equal if all their cells are equal. If columns are specified, rows are equal if the cells in those columns are equal.",
+    example = "ps | distinct ^user ^name"
+)]
+pub struct Distinct {
+    #[unnamed()]
+    #[description("the columns to compare.")]
+    columns: Vec<Field>,
+}
+
+pub fn distinct(context: ExecutionContext) -> CrushResult<()> {
+    let cfg: Distinct = Distinct::parse(context.arguments, &context.printer)?;
+    let mut input = input_stream(&context.input)?;
+    let types = input.types().to_vec();
+    let indices = if cfg.columns.is_empty() {
+        (0..types.len()).collect()
+    } else {
+        cfg.columns
+            .iter()
+            .map(|f| types.as_slice().find(f))
+            .collect::<CrushResult<Vec<_>>>()?
+    };
+    let output = context.output.initialize(types)?;
+    let mut seen: HashSet<Row> = HashSet::new();
+    while let Ok(row) = input.read() {
+        let key = hashable(Row::new(
+            indices
+                .iter()
+                .map(|idx| row.cells()[*idx].clone())
+                .collect(),
+        ))?;
+        if seen.insert(key) {
+            output.send(row)?;
+        }
+    }
+    Ok(())
+}
+
+#[signature(
+    union,
+    can_block = true,
+    short = "Pass on the rows of the io, followed by the rows of other tables or streams",
+    long = "All tables and streams must have the same columns. Like the other set operations, only the first of all rows that are equal is passed on, unless all is true.",
+    example = "find ./a | union (find ./b)"
+)]
+pub struct Union {
+    #[unnamed()]
+    #[description("the tables and streams to add to the io.")]
+    other: Vec<Value>,
+    #[description("pass on rows that are equal to an earlier row, too.")]
+    #[default(false)]
+    all: bool,
+}
+
+pub fn union(context: ExecutionContext) -> CrushResult<()> {
+    let cfg: Union = Union::parse(context.arguments, &context.printer)?;
+    let input = input_stream(&context.input)?;
+    let others = other_streams(cfg.other)?;
+    let output = context
+        .output
+        .initialize(combined_type(input.types(), &others)?)?;
+    let mut seen: HashSet<Row> = HashSet::new();
+    for mut stream in std::iter::once(input).chain(others) {
+        while let Ok(row) = stream.read() {
+            if cfg.all {
+                output.send(row)?;
+            } else {
+                let row = hashable(row)?;
+                if seen.insert(row.clone()) {
+                    output.send(row)?;
+                }
+            }
+        }
+    }
+    Ok(())
+}
+
+#[signature(
+    intersect,
+    can_block = true,
+    short = "Only pass on the rows of the io that are also in every one of the other tables or streams",
+    long = "All tables and streams must have the same columns. Rows are passed on in the order of the io, and only the first of all rows that are equal is passed on. The other tables and streams are read in full before any output is produced.",
+    example = "find ./a | intersect (find ./b)"
+)]
+pub struct Intersect {
+    #[unnamed()]
+    #[description("the tables and streams to intersect the io with.")]
+    other: Vec<Value>,
+}
+
+pub fn intersect(context: ExecutionContext) -> CrushResult<()> {
+    let cfg: Intersect = Intersect::parse(context.arguments, &context.printer)?;
+    let mut input = input_stream(&context.input)?;
+    let others = other_streams(cfg.other)?;
+    let output = context
+        .output
+        .initialize(combined_type(input.types(), &others)?)?;
+    let sets = others
+        .into_iter()
+        .map(read_set)
+        .collect::<CrushResult<Vec<_>>>()?;
+    let mut seen: HashSet<Row> = HashSet::new();
+    while let Ok(row) = input.read() {
+        let row = hashable(row)?;
+        if sets.iter().all(|s| s.contains(&row)) && seen.insert(row.clone()) {
+            output.send(row)?;
+        }
+    }
+    Ok(())
+}
+
+#[signature(
+    except,
+    can_block = true,
+    short = "Only pass on the rows of the io that are in none of the other tables or streams",
+    long = "All tables and streams must have the same columns. Rows are passed on in the order of the io, and only the first of all rows that are equal is passed on. The other tables and streams are read in full before any output is produced.",
+    example = "find ./a | except (find ./b)"
+)]
+pub struct Except {
+    #[unnamed()]
+    #[description("the tables and streams whose rows to remove from the io.")]
+    other: Vec<Value>,
+}
+
+pub fn except(context: ExecutionContext) -> CrushResult<()> {
+    let cfg: Except = Except::parse(context.arguments, &context.printer)?;
+    let mut input = input_stream(&context.input)?;
+    let others = other_streams(cfg.other)?;
+    let output = context
+        .output
+        .initialize(combined_type(input.types(), &others)?)?;
+    let sets = others
+        .into_iter()
+        .map(read_set)
+        .collect::<CrushResult<Vec<_>>>()?;
+    let mut seen: HashSet<Row> = HashSet::new();
+    while let Ok(row) = input.read() {
+        let row = hashable(row)?;
+        if !sets.iter().any(|s| s.contains(&row)) && seen.insert(row.clone()) {
+            output.send(row)?;
+        }
+    }
+    Ok(())
+}
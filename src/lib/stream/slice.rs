@@ -0,0 +1,128 @@
+use crate::lang::argument::ArgumentHandler;
+use crate::lang::command::OutputType::Passthrough;
+use crate::lang::errors::{argument_error, error, CrushResult};
+use crate::lang::execution_context::ExecutionContext;
+use crate::lang::table::Row;
+use rand::Rng;
+use signature::signature;
+
+#[signature(
+    skip,
+    can_block = true,
+    output = Passthrough,
+    short = "Skip the first rows of the io and pass on the rest",
+    example = "find . | skip 100 | head 10"
+)]
+pub struct Skip {
+    #[description("the number of rows to skip.")]
+    rows: usize,
+}
+
+pub fn skip(context: ExecutionContext) -> CrushResult<()> {
+    let cfg: Skip = Skip::parse(context.arguments, &context.printer)?;
+    match context.input.recv()?.stream() {
+        Some(mut input) => {
+            let output = context.output.initialize(input.types().to_vec())?;
+            let mut skipped = 0;
+            while let Ok(row) = input.read() {
+                if skipped < cfg.rows {
+                    skipped += 1;
+                } else {
+                    output.send(row)?;
+                }
+            }
+            Ok(())
+        }
+        None => error("Expected a stream"),
+    }
+}
+
+#[signature(
+    slice,
+    can_block = true,
+    output = Passthrough,
+    short = "Pass on the rows of the io with a row number in the specified range",
+    long = "Rows are numbered from zero. The row numbered from is included, the row numbered to is not, and only every step:th row in between is passed on. Reading stops as soon as the end of the range is reached.",
+    example = "ps | slice 10 20 2"
+)]
+pub struct Slice {
+    #[description("the number of the first row to pass on.")]
+    from: usize,
+    #[description("the number of the row to stop at. By default, the range ends with the io.")]
+    to: Option<usize>,
+    #[description("pass on every step:th row.")]
+    #[default(1usize)]
+    step: usize,
+}
+
+pub fn slice(context: ExecutionContext) -> CrushResult<()> {
+    let cfg: Slice = Slice::parse(context.arguments, &context.printer)?;
+    if cfg.step == 0 {
+        return argument_error("The step must be at least 1");
+    }
+    match context.input.recv()?.stream() {
+        Some(mut input) => {
+            let output = context.output.initialize(input.types().to_vec())?;
+            let mut idx = 0;
+            let mut next = cfg.from;
+            while cfg.to.map(|to| idx < to).unwrap_or(true) {
+                match input.read() {
+                    Ok(row) => {
+                        if idx == next {
+                            output.send(row)?;
+                            next += cfg.step;
+                        }
+                        idx += 1;
+                    }
+                    Err(_) => break,
+                }
+            }
+            Ok(())
+        }
+        None => error("Expected a stream"),
+    }
+}
+
+#[signature(
+    sample,
+    can_block = true,
+    output = Passthrough,
+    short = "Pass on a random sample of the rows of the io",
+    long = "Every row has the same chance of being picked, and the picked rows are passed on in the order they were read. Only the rows picked so far are kept in memory, but nothing is passed on until the io has been read in full.",
+    example = "find . | sample 10"
+)]
+pub struct Sample {
+    #[description("the number of rows to pick.")]
+    #[default(10usize)]
+    rows: usize,
+}
+
+pub fn sample(context: ExecutionContext) -> CrushResult<()> {
+    let cfg: Sample = Sample::parse(context.arguments, &context.printer)?;
+    match context.input.recv()?.stream() {
+        Some(mut input) => {
+            let output = context.output.initialize(input.types().to_vec())?;
+            let mut rng = rand::thread_rng();
+            /* Reservoir sampling, keeping the number of each row to restore the order. */
+            let mut reservoir: Vec<(usize, Row)> = Vec::with_capacity(cfg.rows);
+            let mut idx = 0;
+            while let Ok(row) = input.read() {
+                if reservoir.len() < cfg.rows {
+                    reservoir.push((idx, row));
+                } else {
+                    let pick = rng.gen_range(0, idx + 1);
+                    if pick < cfg.rows {
+                        reservoir[pick] = (idx, row);
+                    }
+                }
+                idx += 1;
+            }
+            reservoir.sort_by_key(|(idx, _)| *idx);
+            for (_, row) in reservoir {
+                output.send(row)?;
+            }
+            Ok(())
+        }
+        None => error("Expected a stream"),
+    }
+}
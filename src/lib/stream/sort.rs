@@ -1,62 +1,359 @@
-use crate::lang::argument::ArgumentHandler;
-use crate::lang::command::OutputType::Passthrough;
-use crate::lang::errors::{error, CrushResult};
+use crate::lang::argument::Argument;
+use crate::lang::errors::{argument_error, data_error, error, to_crush_error, CrushResult};
 use crate::lang::execution_context::ExecutionContext;
-use crate::lang::stream::CrushStream;
-use crate::lang::table::ColumnVec;
-use crate::lang::table::Row;
-use crate::lang::value::Field;
-use crate::{lang::errors::argument_error, lang::stream::OutputStream};
-use signature::signature;
-
-#[signature(
-    sort,
-    can_block=true,
-    short="Sort io based on column",
-    long="ps | sort ^cpu",
-    output=Passthrough)]
-pub struct Sort {
-    #[description("the column to sort on. Not required if there is only one column.")]
-    field: Option<Field>,
-}
-
-pub fn run(idx: usize, input: &mut dyn CrushStream, output: OutputStream) -> CrushResult<()> {
+use crate::lang::list::List;
+use crate::lang::scope::Scope;
+use crate::lang::serialization::{deserialize, serialize};
+use crate::lang::stream::{CrushStream, OutputStream};
+use crate::lang::table::{ColumnType, ColumnVec, Row};
+use crate::lang::value::{Field, Value, ValueType};
+use std::cmp::Ordering;
+use std::convert::TryFrom;
+use std::fs::File;
+use std::io::{BufReader, BufWriter, ErrorKind, Read, Write};
+use std::mem::size_of;
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicUsize, Ordering as AtomicOrdering};
+
+/** The estimated number of bytes of rows that are sorted in memory before they are spilled to disk. */
+const DEFAULT_MEMORY: usize = 256 * 1024 * 1024;
+
+static SPILL_COUNTER: AtomicUsize = AtomicUsize::new(0);
+
+struct Config {
+    keys: Vec<(Option<Field>, bool)>,
+    memory: usize,
+}
+
+/**
+  Named arguments are order dependent, since --reverse applies to the key that follows it,
+  so the arguments are parsed by hand rather than through a signature.
+*/
+fn parse(arguments: Vec<Argument>) -> CrushResult<Config> {
+    let mut keys = Vec::new();
+    let mut reverse = false;
+    let mut memory = DEFAULT_MEMORY;
+    for a in arguments {
+        match (a.argument_type.as_deref(), a.value) {
+            (None, Value::Field(f)) => {
+                keys.push((Some(f), reverse));
+                reverse = false;
+            }
+            (Some("reverse"), Value::Bool(b)) => reverse = b,
+            (Some("memory"), Value::Integer(i)) => {
+                memory = to_crush_error(usize::try_from(i))?;
+                if memory == 0 {
+                    return argument_error("The memory limit must be at least one byte");
+                }
+            }
+            (Some(name), _) => {
+                return argument_error(format!("Unknown argument {}", name).as_str())
+            }
+            (None, v) => {
+                return argument_error(
+                    format!(
+                        "Expected sort keys to be fields, got a value of type {}",
+                        v.value_type().to_string()
+                    )
+                    .as_str(),
+                )
+            }
+        }
+    }
+    if reverse {
+        if !keys.is_empty() {
+            return argument_error("Expected a sort key after --reverse");
+        }
+        keys.push((None, true));
+    }
+    if keys.is_empty() {
+        keys.push((None, false));
+    }
+    Ok(Config { keys, memory })
+}
+
+/**
+  A rough estimate of the memory used by a value: the value itself, the values it holds and the
+  text of strings, but not the bookkeeping of the collections holding them.
+*/
+fn estimated_size(value: &Value) -> usize {
+    size_of::<Value>()
+        + match value {
+            Value::String(s) | Value::Regex(s, _) => s.len(),
+            Value::Field(f) => f.iter().map(|s| size_of::<String>() + s.len()).sum(),
+            Value::File(p) => p.as_os_str().len(),
+            Value::Binary(b) => b.len(),
+            Value::BigInteger(i) => i.bits() / 8,
+            Value::List(l) => l.dump().iter().map(estimated_size).sum(),
+            Value::Dict(d) => d
+                .elements()
+                .iter()
+                .map(|(k, v)| estimated_size(k) + estimated_size(v))
+                .sum(),
+            Value::Struct(s) => s
+                .local_elements()
+                .iter()
+                .map(|(k, v)| k.len() + estimated_size(v))
+                .sum(),
+            Value::Table(t) => t.rows().iter().map(estimated_row_size).sum(),
+            _ => 0,
+        }
+}
+
+fn estimated_row_size(row: &Row) -> usize {
+    row.cells().iter().map(estimated_size).sum()
+}
+
+/**
+  Compare two values. Values that have no natural order relative to each other are still given
+  a consistent one: NaN is sorted after all other floats, and values of different types are
+  ordered by the name of their type.
+*/
+pub fn compare(a: &Value, b: &Value) -> Ordering {
+    if let Some(ordering) = a.partial_cmp(b) {
+        return ordering;
+    }
+    match (a, b) {
+        (Value::Float(a), Value::Float(b)) => a.is_nan().cmp(&b.is_nan()),
+        _ => a
+            .value_type()
+            .to_string()
+            .cmp(&b.value_type().to_string())
+            .then_with(|| a.to_string().cmp(&b.to_string())),
+    }
+}
+
+fn compare_rows(keys: &[(usize, bool)], a: &Row, b: &Row) -> Ordering {
+    for (idx, reverse) in keys {
+        let ordering = compare(&a.cells()[*idx], &b.cells()[*idx]);
+        let ordering = if *reverse {
+            ordering.reverse()
+        } else {
+            ordering
+        };
+        if ordering != Ordering::Equal {
+            return ordering;
+        }
+    }
+    Ordering::Equal
+}
+
+/** A temporary directory holding sorted runs of rows, removed when the sort is done. */
+struct SpillDirectory {
+    path: PathBuf,
+    runs: Vec<PathBuf>,
+}
+
+impl SpillDirectory {
+    fn new() -> CrushResult<SpillDirectory> {
+        let path = std::env::temp_dir().join(format!(
+            "crush-sort-{}-{}",
+            std::process::id(),
+            SPILL_COUNTER.fetch_add(1, AtomicOrdering::SeqCst)
+        ));
+        to_crush_error(std::fs::create_dir(&path))?;
+        Ok(SpillDirectory {
+            path,
+            runs: Vec::new(),
+        })
+    }
+
+    /** Write a sorted run as a sequence of length prefixed, pup serialized rows. */
+    fn spill(&mut self, rows: Vec<Row>) -> CrushResult<()> {
+        let path = self.path.join(format!("run-{}", self.runs.len()));
+        let mut writer = BufWriter::new(to_crush_error(File::create(&path))?);
+        let mut buf = Vec::new();
+        for row in rows {
+            buf.clear();
+            serialize(
+                &Value::List(List::new(ValueType::Any, row.into_vec())),
+                &mut buf,
+            )?;
+            to_crush_error(writer.write_all(&(buf.len() as u64).to_le_bytes()))?;
+            to_crush_error(writer.write_all(&buf))?;
+        }
+        to_crush_error(writer.flush())?;
+        self.runs.push(path);
+        Ok(())
+    }
+}
+
+impl Drop for SpillDirectory {
+    fn drop(&mut self) {
+        let _ = std::fs::remove_dir_all(&self.path);
+    }
+}
+
+struct Run {
+    reader: BufReader<File>,
+}
+
+impl Run {
+    fn next(&mut self, env: &Scope) -> CrushResult<Option<Row>> {
+        let mut len = [0u8; 8];
+        match self.reader.read_exact(&mut len) {
+            Ok(()) => {}
+            Err(e) if e.kind() == ErrorKind::UnexpectedEof => return Ok(None),
+            Err(e) => return to_crush_error(Err(e)),
+        }
+        let mut buf = vec![0u8; u64::from_le_bytes(len) as usize];
+        to_crush_error(self.reader.read_exact(&mut buf))?;
+        match deserialize(&buf, env)? {
+            Value::List(l) => Ok(Some(Row::new(l.dump()))),
+            _ => data_error("Invalid row in sorted run"),
+        }
+    }
+}
+
+/**
+  Merge the sorted runs. Ties are resolved in favour of the earliest run, which keeps the sort
+  stable since the runs were written in input order.
+*/
+fn merge(
+    keys: &[(usize, bool)],
+    spill: &SpillDirectory,
+    output: OutputStream,
+    env: &Scope,
+) -> CrushResult<()> {
+    let mut runs = Vec::new();
+    let mut heads = Vec::new();
+    for path in &spill.runs {
+        let mut run = Run {
+            reader: BufReader::new(to_crush_error(File::open(path))?),
+        };
+        heads.push(run.next(env)?);
+        runs.push(run);
+    }
+    loop {
+        let mut min: Option<usize> = None;
+        for (idx, head) in heads.iter().enumerate() {
+            if let Some(row) = head {
+                min = match min {
+                    Some(m)
+                        if compare_rows(keys, heads[m].as_ref().unwrap(), row)
+                            != Ordering::Greater =>
+                    {
+                        Some(m)
+                    }
+                    _ => Some(idx),
+                };
+            }
+        }
+        match min {
+            None => return Ok(()),
+            Some(idx) => {
+                let next = runs[idx].next(env)?;
+                output.send(std::mem::replace(&mut heads[idx], next).unwrap())?;
+            }
+        }
+    }
+}
+
+pub fn run(
+    keys: &[(usize, bool)],
+    memory: usize,
+    input: &mut dyn CrushStream,
+    output: OutputStream,
+    env: &Scope,
+) -> CrushResult<()> {
     let mut res: Vec<Row> = Vec::new();
+    let mut buffered = 0;
+    let mut spill: Option<SpillDirectory> = None;
     while let Ok(row) = input.read() {
+        buffered += estimated_row_size(&row);
         res.push(row);
+        if buffered >= memory {
+            buffered = 0;
+            res.sort_by(|a, b| compare_rows(keys, a, b));
+            if spill.is_none() {
+                spill = Some(SpillDirectory::new()?);
+            }
+            spill.as_mut().unwrap().spill(std::mem::take(&mut res))?;
+        }
     }
 
-    res.sort_by(|a, b| a.cells()[idx].partial_cmp(&b.cells()[idx]).expect("OH NO!"));
-
-    for row in res {
-        output.send(row)?;
+    res.sort_by(|a, b| compare_rows(keys, a, b));
+    match spill {
+        None => {
+            for row in res {
+                output.send(row)?;
+            }
+            Ok(())
+        }
+        Some(mut spill) => {
+            if !res.is_empty() {
+                spill.spill(res)?;
+            }
+            merge(keys, &spill, output, env)
+        }
     }
+}
 
-    Ok(())
+fn key_index(types: &[ColumnType], field: &Option<Field>) -> CrushResult<usize> {
+    let idx = match field {
+        None => {
+            if types.len() == 1 {
+                0
+            } else {
+                return argument_error("Missing comparison key");
+            }
+        }
+        Some(field) => types.find(field)?,
+    };
+    if types[idx].cell_type.is_comparable() {
+        Ok(idx)
+    } else {
+        argument_error("Bad comparison key")
+    }
 }
 
 pub fn sort(context: ExecutionContext) -> CrushResult<()> {
     match context.input.recv()?.stream() {
         Some(mut input) => {
             let output = context.output.initialize(input.types().to_vec())?;
-            let cfg: Sort = Sort::parse(context.arguments, &context.printer)?;
-            let idx = match cfg.field {
-                None => {
-                    if input.types().len() == 1 {
-                        0
-                    } else {
-                        return argument_error("Missing comparison key");
-                    }
-                }
-                Some(field) => input.types().find(&field)?,
-            };
-
-            if input.types()[idx].cell_type.is_comparable() {
-                run(idx, input.as_mut(), output)
-            } else {
-                argument_error("Bad comparison key")
-            }
+            let cfg = parse(context.arguments)?;
+            let keys = cfg
+                .keys
+                .iter()
+                .map(|(field, reverse)| Ok((key_index(input.types(), field)?, *reverse)))
+                .collect::<CrushResult<Vec<_>>>()?;
+            run(&keys, cfg.memory, input.as_mut(), output, &context.env)
         }
         None => error("Expected a stream"),
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::lang::table::Table;
+
+    #[test]
+    fn incomparable_values_have_a_total_order() {
+        let mut values = [
+            Value::Float(f64::NAN),
+            Value::string("b"),
+            Value::Float(2.0),
+            Value::Integer(1),
+            Value::Float(1.0),
+            Value::string("a"),
+        ];
+        values.sort_by(compare);
+        assert_eq!(
+            values.iter().map(|v| v.to_string()).collect::<Vec<_>>(),
+            vec!["1", "2", "NaN", "1", "a", "b"]
+        );
+    }
+
+    #[test]
+    fn estimated_size_counts_nested_values() {
+        let short = Row::new(vec![Value::Integer(1), Value::string("a")]);
+        let long = Row::new(vec![Value::Integer(1), Value::string(&"a".repeat(1000))]);
+        let nested = Row::new(vec![
+            Value::Integer(1),
+            Value::Table(Table::new(vec![], vec![long.clone(), long.clone()])),
+        ]);
+        assert!(estimated_row_size(&long) >= estimated_row_size(&short) + 999);
+        assert!(estimated_row_size(&nested) >= 2 * estimated_row_size(&long));
+    }
+}
@@ -6,6 +6,9 @@ use crate::lang::{argument::Argument, table::ColumnType};
 use crate::lang::{value::Value, value::ValueType};
 use chrono::Duration;
 use float_ord::FloatOrd;
+use num_bigint::BigInt;
+use num_traits::Zero;
+use std::cmp::Ordering;
 
 fn parse(input_type: &[ColumnType], arguments: &[Argument]) -> CrushResult<usize> {
     match arguments.len() {
@@ -45,7 +48,35 @@ macro_rules! sum_function {
     };
 }
 
-sum_function!(sum_int, i128, 0, Integer);
+/**
+  Sum a column of integers. The sum is kept in an i128 for as long as it fits, and the overflow is
+  moved over to a big integer when it doesn't, so the result is always exact.
+*/
+fn sum_integers(s: &mut Stream, column: usize) -> CrushResult<(BigInt, usize)> {
+    let mut res: i128 = 0;
+    let mut big = BigInt::zero();
+    let mut count = 0;
+    while let Ok(row) = s.read() {
+        count += 1;
+        match &row.cells()[column] {
+            Value::Integer(i) => match res.checked_add(*i) {
+                Some(v) => res = v,
+                None => {
+                    big += res;
+                    res = *i;
+                }
+            },
+            Value::BigInteger(i) => big += i,
+            _ => return error("Invalid cell value"),
+        }
+    }
+    Ok((big + res, count))
+}
+
+fn sum_int(mut s: Stream, column: usize) -> CrushResult<Value> {
+    Ok(Value::from(sum_integers(&mut s, column)?.0))
+}
+
 sum_function!(sum_float, f64, 0.0, Float);
 sum_function!(sum_duration, Duration, Duration::seconds(0), Duration);
 
@@ -88,7 +119,14 @@ macro_rules! avg_function {
     };
 }
 
-avg_function!(avg_int, i128, 0, Integer, i128);
+fn avg_int(mut s: Stream, column: usize) -> CrushResult<Value> {
+    let (sum, count) = sum_integers(&mut s, column)?;
+    if count == 0 {
+        return error("Can't calculate average of an empty stream");
+    }
+    Ok(Value::from(sum / count))
+}
+
 avg_function!(avg_float, f64, 0.0, Float, f64);
 avg_function!(avg_duration, Duration, Duration::seconds(0), Duration, i32);
 
@@ -131,7 +169,22 @@ macro_rules! aggr_function {
     };
 }
 
-aggr_function!(min_int, Integer, |a, b| std::cmp::min(a, b));
+/** Integers can be stored in two different variants, so compare them as values. */
+fn aggr_int(mut s: Stream, column: usize, keep: Ordering) -> CrushResult<Value> {
+    let mut res = s.read()?.cells()[column].clone();
+    while let Ok(row) = s.read() {
+        let value = &row.cells()[column];
+        if value.partial_cmp(&res) == Some(keep) {
+            res = value.clone();
+        }
+    }
+    Ok(res)
+}
+
+fn min_int(s: Stream, column: usize) -> CrushResult<Value> {
+    aggr_int(s, column, Ordering::Less)
+}
+
 aggr_function!(min_float, Float, |a, b| std::cmp::min(
     FloatOrd(a),
     FloatOrd(b)
@@ -140,7 +193,10 @@ aggr_function!(min_float, Float, |a, b| std::cmp::min(
 aggr_function!(min_duration, Duration, |a, b| std::cmp::min(a, b));
 aggr_function!(min_time, Time, |a, b| std::cmp::min(a, b));
 
-aggr_function!(max_int, Integer, |a, b| std::cmp::max(a, b));
+fn max_int(s: Stream, column: usize) -> CrushResult<Value> {
+    aggr_int(s, column, Ordering::Greater)
+}
+
 aggr_function!(max_float, Float, |a, b| std::cmp::max(
     FloatOrd(a),
     FloatOrd(b)
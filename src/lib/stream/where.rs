@@ -1,11 +1,12 @@
 use crate::lang::argument::ArgumentHandler;
 use crate::lang::command::Command;
 use crate::lang::command::OutputType::Passthrough;
-use crate::lang::errors::{error, CrushResult};
+use crate::lang::errors::{argument_error, error, CrushResult};
 use crate::lang::execution_context::ExecutionContext;
 use crate::lang::stream::{black_hole, channels, empty_channel};
 use crate::lang::{argument::Argument, table::ColumnType};
 use crate::lang::{table::Row, value::Value};
+use crate::lib::stream::parallel;
 use signature::signature;
 
 #[signature(
@@ -14,10 +15,14 @@ can_block = true,
 output = Passthrough,
 short = "Filter out rows from io based on condition",
 long = "The columns of the row are exported to the environment using the column names.",
+long = "With parallel set, the condition is evaluated for several rows at a time on separate threads, which helps when it is slow, e.g. because it accesses the network. Rows are still output in input order. With or without parallel, the first row, in input order, that the condition fails on stops the filtering, and its error is reported once.",
 example = "ps | where {status != \"Sleeping\"}")]
 pub struct Where {
     #[description("the condition to filter on.")]
     condition: Command,
+    #[description("the number of threads to evaluate the condition on.")]
+    #[default(1usize)]
+    parallel: usize,
 }
 
 fn evaluate(
@@ -63,14 +68,38 @@ pub fn r#where(context: ExecutionContext) -> CrushResult<()> {
                 printer: context.printer.clone(),
             };
             let output = context.output.initialize(input.types().to_vec())?;
-            while let Ok(row) = input.read() {
-                match evaluate(cfg.condition.copy(), &row, input.types(), &base_context) {
-                    Ok(val) => {
-                        if val && output.send(row).is_err() {
-                            break;
+            if cfg.parallel == 0 {
+                return argument_error("parallel must be at least 1");
+            }
+            if cfg.parallel > 1 {
+                let workers = (0..cfg.parallel)
+                    .map(|_| {
+                        let condition = cfg.condition.copy();
+                        let input_type = input.types().to_vec();
+                        let base_context = base_context.clone();
+                        move |row: &Row| {
+                            parallel::captured(&base_context.printer, |printer| {
+                                let context = ExecutionContext {
+                                    printer: printer.clone(),
+                                    ..base_context.clone()
+                                };
+                                evaluate(condition.copy(), row, &input_type, &context)
+                            })
                         }
+                    })
+                    .collect();
+                return parallel::map("where-worker", input, workers, |row, val| {
+                    if val {
+                        output.send(row)?;
                     }
-                    Err(e) => base_context.printer.crush_error(e),
+                    Ok(())
+                });
+            }
+            while let Ok(row) = input.read() {
+                if evaluate(cfg.condition.copy(), &row, input.types(), &base_context)?
+                    && output.send(row).is_err()
+                {
+                    break;
                 }
             }
             Ok(())
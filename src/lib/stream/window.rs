@@ -0,0 +1,427 @@
+use crate::lang::argument::ArgumentHandler;
+use crate::lang::command::InputType;
+use crate::lang::errors::{argument_error, data_error, error, CrushResult};
+use crate::lang::execution_context::ExecutionContext;
+use crate::lang::stream::{CrushStream, OutputStream};
+use crate::lang::table::{ColumnType, ColumnVec, Row};
+use crate::lang::value::{Field, Value, ValueType};
+use chrono::Duration;
+use signature::signature;
+use std::cmp::Ordering;
+use std::collections::{HashMap, VecDeque};
+
+#[signature(
+    window,
+    can_block = true,
+    input = InputType::Table,
+    short = "Add window functions, like running aggregates, to each row as new columns",
+    long = "Every input row is output with one extra column per window function. Aggregates are calculated over the frame of the row, which by default is the row itself and all preceding rows of its partition. Use rows or range to only look at the latest rows.",
+    long = "Rows are output in input order, as soon as all their columns are known. The input is never sorted, so if an order column is given, the input must already be sorted on it within each partition.",
+    long = "The new columns are named after the window function and the column it is applied to, e.g. sum_size, and come in the order row_number, rank, sum, avg, min, max, lag and lead.",
+    example = "ps | sort ^user | window partition=^user order=^user rank=true sum=^cpu"
+)]
+pub struct Window {
+    #[description("the columns to partition rows by. Window functions only look at rows of the same partition.")]
+    partition: Vec<Field>,
+    #[description("the column that the input is sorted on, used by rank and range.")]
+    order: Option<Field>,
+    #[description("limit the frame to this many rows, ending with the current row.")]
+    rows: Option<usize>,
+    #[description("limit the frame to rows whose order column, a time, is at most this much older than that of the current row.")]
+    range: Option<Duration>,
+    #[description("add a running sum of these columns.")]
+    sum: Vec<Field>,
+    #[description("add a running average of these columns.")]
+    avg: Vec<Field>,
+    #[description("add a running minimum of these columns.")]
+    min: Vec<Field>,
+    #[description("add a running maximum of these columns.")]
+    max: Vec<Field>,
+    #[description("add the value these columns had offset rows earlier in the partition.")]
+    lag: Vec<Field>,
+    #[description("add the value these columns have offset rows later in the partition.")]
+    lead: Vec<Field>,
+    #[description("the number of rows lag and lead look back and ahead, at least 1.")]
+    #[default(1usize)]
+    offset: usize,
+    #[description("add the rank of each row within its partition, by the order column.")]
+    #[default(false)]
+    rank: bool,
+    #[description("add the number of each row within its partition, starting at 1.")]
+    #[default(false)]
+    row_number: bool,
+}
+
+#[derive(Clone, Copy)]
+enum Aggregate {
+    Sum,
+    Avg,
+    Min,
+    Max,
+}
+
+impl Aggregate {
+    fn name(self) -> &'static str {
+        match self {
+            Aggregate::Sum => "sum",
+            Aggregate::Avg => "avg",
+            Aggregate::Min => "min",
+            Aggregate::Max => "max",
+        }
+    }
+
+    fn check(self, cell_type: &ValueType) -> CrushResult<()> {
+        let ok = match self {
+            Aggregate::Sum | Aggregate::Avg => matches!(
+                cell_type,
+                ValueType::Integer | ValueType::Float | ValueType::Duration
+            ),
+            Aggregate::Min | Aggregate::Max => matches!(
+                cell_type,
+                ValueType::Integer | ValueType::Float | ValueType::Duration | ValueType::Time
+            ),
+        };
+        if ok {
+            Ok(())
+        } else {
+            argument_error(
+                format!(
+                    "Can't calculate {} of elements of type {}",
+                    self.name(),
+                    cell_type.to_string()
+                )
+                .as_str(),
+            )
+        }
+    }
+
+    /** Fold one more value into the running value of the aggregate. Averages are kept as sums. */
+    fn add(self, acc: Option<Value>, value: &Value) -> CrushResult<Option<Value>> {
+        Ok(Some(match acc {
+            None => value.clone(),
+            Some(acc) => match self {
+                Aggregate::Sum | Aggregate::Avg => add(acc, value)?,
+                Aggregate::Min => keep(acc, value, Ordering::Less),
+                Aggregate::Max => keep(acc, value, Ordering::Greater),
+            },
+        }))
+    }
+
+    fn finish(self, acc: Option<Value>, count: usize) -> CrushResult<Value> {
+        match (self, acc) {
+            (_, None) => Ok(Value::Empty()),
+            (Aggregate::Avg, Some(sum)) => divide(sum, count),
+            (_, Some(acc)) => Ok(acc),
+        }
+    }
+}
+
+fn add(a: Value, b: &Value) -> CrushResult<Value> {
+    match (a, b) {
+        (Value::Integer(a), Value::Integer(b)) => Ok(match a.checked_add(*b) {
+            Some(sum) => Value::Integer(sum),
+            None => Value::from(num_bigint::BigInt::from(a) + *b),
+        }),
+        (Value::Float(a), Value::Float(b)) => Ok(Value::Float(a + b)),
+        (Value::Duration(a), Value::Duration(b)) => Ok(Value::Duration(a + *b)),
+        (a, b) => match (a.big_integer(), b.big_integer()) {
+            (Some(a), Some(b)) => Ok(Value::from(a + b)),
+            _ => data_error("Invalid cell value"),
+        },
+    }
+}
+
+fn divide(sum: Value, count: usize) -> CrushResult<Value> {
+    match sum {
+        Value::Float(f) => Ok(Value::Float(f / count as f64)),
+        Value::Duration(d) => Ok(Value::Duration(d / count as i32)),
+        v => match v.big_integer() {
+            Some(i) => Ok(Value::from(i / count)),
+            None => data_error("Invalid cell value"),
+        },
+    }
+}
+
+fn keep(acc: Value, value: &Value, keep: Ordering) -> Value {
+    if value.partial_cmp(&acc) == Some(keep) {
+        value.clone()
+    } else {
+        acc
+    }
+}
+
+enum Frame {
+    Unbounded,
+    Rows(usize),
+    Range(usize, Duration),
+}
+
+struct Config {
+    partition: Vec<usize>,
+    order: Option<usize>,
+    frame: Frame,
+    aggregates: Vec<(Aggregate, usize)>,
+    lag: Vec<usize>,
+    lead: Vec<usize>,
+    offset: usize,
+    rank: bool,
+    row_number: bool,
+}
+
+#[derive(Default)]
+struct Partition {
+    count: usize,
+    rank: usize,
+    last_order: Option<Value>,
+    /** The rows of a bounded frame. */
+    frame: VecDeque<Row>,
+    /** The running values of the aggregates of an unbounded frame. */
+    running: Vec<Option<Value>>,
+    /** The latest rows, for lag. */
+    history: VecDeque<Row>,
+    /** Output rows that are waiting for their lead values, by index in the output queue. */
+    waiting: VecDeque<usize>,
+}
+
+struct Pending {
+    cells: Vec<Value>,
+    complete: bool,
+}
+
+/** Output rows in input order, holding on to rows that don't know their lead values yet. */
+struct Queue {
+    rows: VecDeque<Pending>,
+    first: usize,
+}
+
+impl Queue {
+    fn push(&mut self, cells: Vec<Value>, complete: bool) -> usize {
+        self.rows.push_back(Pending { cells, complete });
+        self.first + self.rows.len() - 1
+    }
+
+    fn get(&mut self, idx: usize) -> &mut Pending {
+        &mut self.rows[idx - self.first]
+    }
+
+    fn flush(&mut self, output: &OutputStream, all: bool) -> CrushResult<()> {
+        while let Some(row) = self.rows.front() {
+            if !row.complete && !all {
+                break;
+            }
+            output.send(Row::new(self.rows.pop_front().unwrap().cells))?;
+            self.first += 1;
+        }
+        Ok(())
+    }
+}
+
+fn time(row: &Row, idx: usize) -> CrushResult<chrono::DateTime<chrono::Local>> {
+    match &row.cells()[idx] {
+        Value::Time(t) => Ok(*t),
+        _ => data_error("Expected the order column to be a time"),
+    }
+}
+
+fn window_functions(
+    cfg: &Config,
+    partition: &mut Partition,
+    row: &Row,
+    cells: &mut Vec<Value>,
+) -> CrushResult<()> {
+    if let Some(order) = cfg.order {
+        let value = &row.cells()[order];
+        match &partition.last_order {
+            Some(last) if value.partial_cmp(last) == Some(Ordering::Equal) => {}
+            Some(last) if value.partial_cmp(last) != Some(Ordering::Greater) => {
+                return data_error("The input is not sorted on the order column, sort it first")
+            }
+            _ => partition.rank = partition.count + 1,
+        }
+        partition.last_order = Some(value.clone());
+    }
+    partition.count += 1;
+    if cfg.row_number {
+        cells.push(Value::Integer(partition.count as i128));
+    }
+    if cfg.rank {
+        cells.push(Value::Integer(partition.rank as i128));
+    }
+
+    match cfg.frame {
+        Frame::Unbounded => {
+            partition.running.resize(cfg.aggregates.len(), None);
+            for (idx, (aggregate, column)) in cfg.aggregates.iter().enumerate() {
+                let acc = partition.running[idx].take();
+                partition.running[idx] = aggregate.add(acc, &row.cells()[*column])?;
+                cells.push(aggregate.finish(partition.running[idx].clone(), partition.count)?);
+            }
+        }
+        Frame::Rows(_) | Frame::Range(_, _) => {
+            partition.frame.push_back(row.clone());
+            match cfg.frame {
+                Frame::Rows(rows) => {
+                    while partition.frame.len() > rows {
+                        partition.frame.pop_front();
+                    }
+                }
+                Frame::Range(order, range) => {
+                    let start = time(row, order)? - range;
+                    while time(&partition.frame[0], order)? < start {
+                        partition.frame.pop_front();
+                    }
+                }
+                Frame::Unbounded => {}
+            }
+            for (aggregate, column) in &cfg.aggregates {
+                let mut acc = None;
+                for r in &partition.frame {
+                    acc = aggregate.add(acc, &r.cells()[*column])?;
+                }
+                cells.push(aggregate.finish(acc, partition.frame.len())?);
+            }
+        }
+    }
+
+    if !cfg.lag.is_empty() {
+        for column in &cfg.lag {
+            cells.push(if partition.history.len() >= cfg.offset {
+                partition.history[partition.history.len() - cfg.offset].cells()[*column].clone()
+            } else {
+                Value::Empty()
+            });
+        }
+        partition.history.push_back(row.clone());
+        if partition.history.len() > cfg.offset {
+            partition.history.pop_front();
+        }
+    }
+    Ok(())
+}
+
+fn run(cfg: Config, input: &mut dyn CrushStream, output: OutputStream) -> CrushResult<()> {
+    let mut partitions: HashMap<Vec<Value>, Partition> = HashMap::new();
+    let mut queue = Queue {
+        rows: VecDeque::new(),
+        first: 0,
+    };
+    let lead_start = input.types().len()
+        + cfg.row_number as usize
+        + cfg.rank as usize
+        + cfg.aggregates.len()
+        + cfg.lag.len();
+
+    while let Ok(row) = input.read() {
+        let key = cfg
+            .partition
+            .iter()
+            .map(|idx| row.cells()[*idx].clone())
+            .collect::<Vec<_>>();
+        let partition = partitions.entry(key).or_default();
+        let mut cells = row.cells().clone();
+        window_functions(&cfg, partition, &row, &mut cells)?;
+
+        if cfg.lead.is_empty() {
+            queue.push(cells, true);
+        } else {
+            if partition.waiting.len() == cfg.offset {
+                let earlier = queue.get(partition.waiting.pop_front().unwrap());
+                for (idx, column) in cfg.lead.iter().enumerate() {
+                    earlier.cells[lead_start + idx] = row.cells()[*column].clone();
+                }
+                earlier.complete = true;
+            }
+            cells.extend(cfg.lead.iter().map(|_| Value::Empty()));
+            partition
+                .waiting
+                .push_back(queue.push(cells, false));
+        }
+        queue.flush(&output, false)?;
+    }
+    queue.flush(&output, true)
+}
+
+fn find(types: &[ColumnType], fields: &[Field]) -> CrushResult<Vec<usize>> {
+    fields.iter().map(|f| types.find(f)).collect()
+}
+
+fn column_name(function: &str, field: &Field) -> String {
+    format!("{}_{}", function, field.join("_"))
+}
+
+pub fn window(context: ExecutionContext) -> CrushResult<()> {
+    let cfg: Window = Window::parse(context.arguments, &context.printer)?;
+    let mut input = match context.input.recv()?.stream() {
+        Some(input) => input,
+        None => return error("Expected a stream"),
+    };
+    let types = input.types().to_vec();
+
+    let order = match &cfg.order {
+        Some(f) => Some(types.as_slice().find(f)?),
+        None => None,
+    };
+    if cfg.rank && order.is_none() {
+        return argument_error("rank requires an order column");
+    }
+    if cfg.offset == 0 {
+        return argument_error("The offset of lag and lead must be at least 1");
+    }
+    let frame = match (cfg.rows, cfg.range) {
+        (None, None) => Frame::Unbounded,
+        (Some(0), None) => return argument_error("The frame must contain at least one row"),
+        (Some(rows), None) => Frame::Rows(rows),
+        (None, Some(range)) => match order {
+            Some(order) if types[order].cell_type == ValueType::Time => Frame::Range(order, range),
+            _ => return argument_error("range requires an order column of type time"),
+        },
+        (Some(_), Some(_)) => return argument_error("Specify either rows or range, not both"),
+    };
+
+    let mut output_types = types.clone();
+    if cfg.row_number {
+        output_types.push(ColumnType::new("row_number", ValueType::Integer));
+    }
+    if cfg.rank {
+        output_types.push(ColumnType::new("rank", ValueType::Integer));
+    }
+    let mut aggregates = Vec::new();
+    for (aggregate, fields) in [
+        (Aggregate::Sum, &cfg.sum),
+        (Aggregate::Avg, &cfg.avg),
+        (Aggregate::Min, &cfg.min),
+        (Aggregate::Max, &cfg.max),
+    ] {
+        for field in fields {
+            let column = types.as_slice().find(field)?;
+            aggregate.check(&types[column].cell_type)?;
+            output_types.push(ColumnType::new(
+                &column_name(aggregate.name(), field),
+                types[column].cell_type.clone(),
+            ));
+            aggregates.push((aggregate, column));
+        }
+    }
+    for (name, fields) in [("lag", &cfg.lag), ("lead", &cfg.lead)] {
+        for field in fields {
+            output_types.push(ColumnType::new(&column_name(name, field), ValueType::Any));
+        }
+    }
+
+    let output = context.output.initialize(output_types)?;
+    run(
+        Config {
+            partition: find(&types, &cfg.partition)?,
+            order,
+            frame,
+            aggregates,
+            lag: find(&types, &cfg.lag)?,
+            lead: find(&types, &cfg.lead)?,
+            offset: cfg.offset,
+            rank: cfg.rank,
+            row_number: cfg.row_number,
+        },
+        input.as_mut(),
+        output,
+    )
+}
@@ -6,6 +6,8 @@ use crate::lang::execution_context::{ArgumentVector, This};
 use crate::lang::value::ValueType;
 use crate::lang::{execution_context::ExecutionContext, value::Value};
 use lazy_static::lazy_static;
+use num_bigint::BigInt;
+use num_traits::ToPrimitive;
 use ordered_map::OrderedMap;
 
 fn full(name: &'static str) -> Vec<&'static str> {
@@ -88,6 +90,9 @@ binary_op!(
     Integer,
     Float,
     |a, b| a + (b as f64),
+    BigInteger,
+    Float,
+    |a, b: BigInt| a + b.to_f64().unwrap_or(f64::NAN),
     Float,
     Float,
     |a, b| a + b
@@ -98,6 +103,9 @@ binary_op!(
     Integer,
     Float,
     |a, b| a - (b as f64),
+    BigInteger,
+    Float,
+    |a, b: BigInt| a - b.to_f64().unwrap_or(f64::NAN),
     Float,
     Float,
     |a, b| a - b
@@ -108,6 +116,9 @@ binary_op!(
     Integer,
     Float,
     |a, b| a * (b as f64),
+    BigInteger,
+    Float,
+    |a, b: BigInt| a * b.to_f64().unwrap_or(f64::NAN),
     Float,
     Float,
     |a, b| a * b
@@ -118,6 +129,9 @@ binary_op!(
     Integer,
     Float,
     |a, b| a / (b as f64),
+    BigInteger,
+    Float,
+    |a, b: BigInt| a / b.to_f64().unwrap_or(f64::NAN),
     Float,
     Float,
     |a, b| a / b
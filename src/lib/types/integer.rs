@@ -1,11 +1,14 @@
 use crate::lang::command::Command;
 use crate::lang::command::OutputType::Known;
 use crate::lang::command::TypeMap;
-use crate::lang::errors::{argument_error, CrushResult};
+use crate::lang::errors::{argument_error, data_error, CrushResult};
 use crate::lang::execution_context::{ArgumentVector, This};
 use crate::lang::value::ValueType;
 use crate::lang::{execution_context::ExecutionContext, value::Value};
 use lazy_static::lazy_static;
+use num_bigint::BigInt;
+use num_integer::Integer;
+use num_traits::{ToPrimitive, Zero};
 use ordered_map::OrderedMap;
 
 fn full(name: &'static str) -> Vec<&'static str> {
@@ -82,52 +85,90 @@ lazy_static! {
     };
 }
 
-binary_op!(
-    add,
-    integer,
-    Integer,
-    Integer,
-    |a, b| a + b,
-    Float,
-    Float,
-    |a, b| a as f64 + b
-);
-binary_op!(
-    sub,
-    integer,
-    Integer,
-    Integer,
-    |a, b| a - b,
-    Float,
-    Float,
-    |a, b| a as f64 - b
-);
-binary_op!(
-    mul,
-    integer,
-    Integer,
-    Integer,
-    |a, b| a * b,
-    Float,
-    Float,
-    |a, b| a as f64 * b
-);
-binary_op!(
-    div,
-    integer,
-    Integer,
-    Integer,
-    |a, b| a / b,
-    Float,
-    Float,
-    |a, b| a as f64 / b
-);
-binary_op!(rem, integer, Integer, Integer, |a, b| a % b);
-binary_op!(r#mod, integer, Integer, Integer, |a, b| (a % b + b) % b);
+/**
+  Arithmetic on integers is done using i128 whenever possible. If the result overflows, or one of
+  the operands is already a big integer, the calculation is redone using arbitrary precision, so
+  integer arithmetic is always exact.
+*/
+fn arithmetic(
+    mut context: ExecutionContext,
+    small: fn(i128, i128) -> Option<i128>,
+    big: fn(BigInt, BigInt) -> CrushResult<BigInt>,
+    float: fn(f64, f64) -> f64,
+) -> CrushResult<()> {
+    context.arguments.check_len(1)?;
+    let this = context.this.big_integer()?;
+    let res = match (this.to_i128(), context.arguments.value(0)?) {
+        (Some(a), Value::Integer(b)) => match small(a, b) {
+            Some(v) => Value::Integer(v),
+            None => Value::from(big(this, BigInt::from(b))?),
+        },
+        (_, Value::BigInteger(b)) => Value::from(big(this, b)?),
+        (_, Value::Integer(b)) => Value::from(big(this, BigInt::from(b))?),
+        (_, Value::Float(b)) => Value::Float(float(this.to_f64().unwrap_or(f64::NAN), b)),
+        _ => return argument_error("Expected only arguments of the same type"),
+    };
+    context.output.send(res)
+}
+
+fn check_divisor(b: &BigInt) -> CrushResult<()> {
+    if b.is_zero() {
+        data_error("Division by zero")
+    } else {
+        Ok(())
+    }
+}
+
+fn add(context: ExecutionContext) -> CrushResult<()> {
+    arithmetic(context, i128::checked_add, |a, b| Ok(a + b), |a, b| a + b)
+}
+
+fn sub(context: ExecutionContext) -> CrushResult<()> {
+    arithmetic(context, i128::checked_sub, |a, b| Ok(a - b), |a, b| a - b)
+}
+
+fn mul(context: ExecutionContext) -> CrushResult<()> {
+    arithmetic(context, i128::checked_mul, |a, b| Ok(a * b), |a, b| a * b)
+}
+
+fn div(context: ExecutionContext) -> CrushResult<()> {
+    arithmetic(
+        context,
+        i128::checked_div,
+        |a, b| {
+            check_divisor(&b)?;
+            Ok(a / b)
+        },
+        |a, b| a / b,
+    )
+}
+
+fn rem(context: ExecutionContext) -> CrushResult<()> {
+    arithmetic(
+        context,
+        i128::checked_rem,
+        |a, b| {
+            check_divisor(&b)?;
+            Ok(a % b)
+        },
+        |a, b| a % b,
+    )
+}
+
+fn r#mod(context: ExecutionContext) -> CrushResult<()> {
+    arithmetic(
+        context,
+        |a, b| a.checked_rem(b)?.checked_add(b)?.checked_rem(b),
+        |a, b| {
+            check_divisor(&b)?;
+            Ok(a.mod_floor(&b))
+        },
+        |a, b| (a % b + b) % b,
+    )
+}
 
 fn neg(context: ExecutionContext) -> CrushResult<()> {
     context.arguments.check_len(0)?;
-    context
-        .output
-        .send(Value::Integer(-context.this.integer()?))
+    let this = context.this.big_integer()?;
+    context.output.send(Value::from(-this))
 }
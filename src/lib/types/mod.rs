@@ -102,6 +102,14 @@ pub fn r#typeof(mut context: ExecutionContext) -> CrushResult<()> {
         .send(Value::Type(context.arguments.value(0)?.value_type()))
 }
 
+pub fn typecheck(mut context: ExecutionContext) -> CrushResult<()> {
+    context.arguments.check_len(1)?;
+    let output_type = context.arguments.command(0)?.typecheck()?;
+    context
+        .output
+        .send(Value::Type(output_type.unwrap_or(ValueType::Any)))
+}
+
 fn class_set(mut context: ExecutionContext) -> CrushResult<()> {
     let this = context.this.r#struct()?;
     let value = context.arguments.value(1)?;
@@ -173,6 +181,20 @@ pub fn declare(root: &Scope) -> CrushResult<()> {
                                 "Return the type of the specified value",
                                 None, Known(ValueType::Type))?;
 
+            env.declare_command("typecheck", typecheck, false,
+                                "typecheck command:command",
+                                "Check the jobs of a closure without running them, and return the type of their output",
+                                Some(r#"    Every command of every job is looked up, and the type of its output is passed
+    on as the input of the next command. Errors like unknown columns, aggregating a
+    column of the wrong type, or passing a table to a command that expects binary
+    input are reported without running anything. The type is any where it can not
+    be known in advance, e.g. for external commands.
+
+    Example:
+
+    typecheck {ps | sort ^cpu}"#),
+                                Known(ValueType::Type))?;
+
             env.declare_command(
                 "class", class, false,
                 "class [parent:type]",
@@ -238,6 +260,7 @@ pub fn declare(root: &Scope) -> CrushResult<()> {
             env.declare("table", Value::Type(ValueType::Table(vec![])))?;
             env.declare("table_stream", Value::Type(ValueType::TableStream(vec![])))?;
             env.declare("struct", Value::Type(ValueType::Struct))?;
+            env.declare("error", Value::Type(ValueType::Error))?;
             Ok(())
         }))?;
     root.r#use(&e);
@@ -7,16 +7,18 @@ mod util;
 
 use rustyline;
 
+use crate::lang::completion::CrushHelper;
 use crate::lang::errors::{to_crush_error, CrushResult};
+use crate::lang::job::jobs;
 use crate::lang::pretty_printer::create_pretty_printer;
 use crate::lang::printer::Printer;
 use crate::lang::scope::Scope;
 use crate::lang::stream::ValueSender;
-use crate::lang::{execute, printer};
+use crate::lang::{execute, parser, printer};
 use crate::util::file::home;
 use lib::declare;
 use rustyline::error::ReadlineError;
-use rustyline::Editor;
+use rustyline::{CompletionType, Config, Editor};
 use std::io::Read;
 use std::path::{Path, PathBuf};
 
@@ -37,19 +39,44 @@ fn run_interactive(
     printer.line("Welcome to Crush");
     printer.line(r#"Type "help" for... help."#);
 
-    let mut rl = Editor::<()>::new();
+    let mut rl = Editor::<CrushHelper>::with_config(
+        Config::builder()
+            .completion_type(CompletionType::List)
+            .build(),
+    );
+    rl.set_helper(Some(CrushHelper::new(global_env.clone())));
     let _ = rl.load_history(&crush_history_file());
+    let mut buffer = String::new();
     loop {
-        let readline = rl.readline("crush# ");
+        if buffer.is_empty() {
+            jobs().report(printer);
+        }
+        let readline = rl.readline(if buffer.is_empty() {
+            "crush# "
+        } else {
+            "     > "
+        });
 
         match readline {
-            Ok(cmd) => {
+            Ok(line) => {
+                buffer.push_str(&line);
+                if parser::is_incomplete(&buffer) {
+                    buffer.push('\n');
+                    continue;
+                }
+                let cmd = std::mem::take(&mut buffer);
                 if !cmd.is_empty() {
                     rl.add_history_entry(cmd.as_str());
-                    execute::string(global_env.clone(), &cmd.as_str(), &printer, pretty_printer);
+                    execute::string(
+                        global_env.clone(),
+                        &cmd.as_str(),
+                        &printer,
+                        &pretty_printer.tty(),
+                    );
                 }
             }
             Err(ReadlineError::Interrupted) => {
+                buffer.clear();
                 printer.line("^C");
             }
             Err(ReadlineError::Eof) => {
@@ -0,0 +1,74 @@
+use lazy_static::lazy_static;
+use std::collections::{HashMap, HashSet};
+use std::os::unix::fs::PermissionsExt;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use std::time::SystemTime;
+
+struct Directory {
+    modified: Option<SystemTime>,
+    executables: HashSet<String>,
+}
+
+lazy_static! {
+    static ref DIRECTORIES: Mutex<HashMap<PathBuf, Directory>> = Mutex::new(HashMap::new());
+}
+
+/** True if the path is a regular file with at least one of the execute bits set. */
+pub fn is_executable(path: &Path) -> bool {
+    match path.metadata() {
+        Ok(meta) => meta.is_file() && meta.permissions().mode() & 0o111 != 0,
+        Err(_) => false,
+    }
+}
+
+fn modified(dir: &Path) -> Option<SystemTime> {
+    dir.metadata().and_then(|m| m.modified()).ok()
+}
+
+fn scan(dir: &Path) -> HashSet<String> {
+    match dir.read_dir() {
+        Ok(entries) => entries
+            .filter_map(|e| e.ok())
+            .filter(|e| is_executable(&e.path()))
+            .filter_map(|e| e.file_name().to_str().map(|s| s.to_string()))
+            .collect(),
+        Err(_) => HashSet::new(),
+    }
+}
+
+/**
+  Find an executable with the specified name in the first of the specified directories that
+  contains one.
+
+  The executables of each directory are cached. A directory is scanned again whenever its
+  modification time changes, so executables that are installed while the shell is running are
+  found.
+*/
+pub fn find(name: &str, dirs: &[PathBuf]) -> Option<PathBuf> {
+    let mut cache = DIRECTORIES.lock().unwrap();
+    for dir in dirs {
+        let current = modified(dir);
+        let stale = match cache.get(dir) {
+            Some(entry) => entry.modified != current,
+            None => true,
+        };
+        if stale {
+            cache.insert(
+                dir.clone(),
+                Directory {
+                    modified: current,
+                    executables: scan(dir),
+                },
+            );
+        }
+        if cache[dir].executables.contains(name) {
+            let full = dir.join(name);
+            // Modification times have a limited resolution, so the cache may be slightly out of date
+            if is_executable(&full) {
+                return Some(full);
+            }
+        }
+    }
+    None
+}
@@ -1,6 +1,8 @@
+pub mod executable;
 pub mod file;
 pub mod glob;
 pub mod identity_arc;
+pub mod pty;
 pub mod regex;
 pub mod replace;
 pub mod thread;
@@ -0,0 +1,400 @@
+use crate::lang::errors::{to_crush_error, CrushResult};
+use crate::lang::job::{format_status, jobs, JobJoinHandle, JobStatus, Stopped};
+use crate::lang::printer::Printer;
+use crate::util::thread::{build, handle};
+use lazy_static::lazy_static;
+use nix::errno::Errno;
+use nix::fcntl::OFlag;
+use nix::libc;
+use nix::poll::{poll, PollFd, PollFlags};
+use nix::pty::{openpty, Winsize};
+use nix::sys::signal::{killpg, sigaction, SaFlags, SigAction, SigHandler, SigSet, Signal};
+use nix::sys::wait::{waitpid, WaitPidFlag, WaitStatus};
+use nix::unistd::{close, dup, pipe2, read, setsid, write, Pid};
+use std::io::Write;
+use std::iter::once;
+use std::os::unix::io::{FromRawFd, RawFd};
+use std::os::unix::process::{CommandExt, ExitStatusExt};
+use std::process::{Command, ExitStatus, Stdio};
+use std::sync::atomic::{AtomicI32, AtomicUsize, Ordering};
+use termion::raw::IntoRawMode;
+
+const STDIN: RawFd = 0;
+
+nix::ioctl_write_ptr_bad!(set_window_size, libc::TIOCSWINSZ, Winsize);
+
+/** True if Crush itself is talking to a terminal, so that there is a terminal to hand over. */
+pub fn is_available() -> bool {
+    termion::is_tty(&std::io::stdin()) && termion::is_tty(&std::io::stdout())
+}
+
+fn window_size() -> CrushResult<Winsize> {
+    let (cols, rows) = to_crush_error(termion::terminal_size())?;
+    Ok(Winsize {
+        ws_row: rows,
+        ws_col: cols,
+        ws_xpixel: 0,
+        ws_ypixel: 0,
+    })
+}
+
+lazy_static! {
+    /** The read end of a pipe that a byte is written to whenever the terminal changes size. */
+    static ref WINDOW_CHANGES: CrushResult<RawFd> = watch_window_size();
+}
+
+static WINDOW_CHANGES_WRITER: AtomicI32 = AtomicI32::new(-1);
+static PREVIOUS_HANDLER: AtomicUsize = AtomicUsize::new(0);
+
+extern "C" fn on_window_change(signal: libc::c_int) {
+    // Only async-signal-safe calls are allowed here. If the pipe is full, a change is pending anyway
+    unsafe {
+        libc::write(
+            WINDOW_CHANGES_WRITER.load(Ordering::Relaxed),
+            [0u8].as_ptr() as *const libc::c_void,
+            1,
+        );
+    }
+    // Line editing has a handler of its own, which still needs to know about the change
+    let previous = PREVIOUS_HANDLER.load(Ordering::Relaxed);
+    if previous != 0 {
+        let previous: extern "C" fn(libc::c_int) = unsafe { std::mem::transmute(previous) };
+        previous(signal);
+    }
+}
+
+fn watch_window_size() -> CrushResult<RawFd> {
+    let (reader, writer) = to_crush_error(pipe2(OFlag::O_NONBLOCK | OFlag::O_CLOEXEC))?;
+    WINDOW_CHANGES_WRITER.store(writer, Ordering::Relaxed);
+    let action = SigAction::new(
+        SigHandler::Handler(on_window_change),
+        SaFlags::SA_RESTART,
+        SigSet::empty(),
+    );
+    let previous = to_crush_error(unsafe { sigaction(Signal::SIGWINCH, &action) })?;
+    if let SigHandler::Handler(previous) = previous.handler() {
+        PREVIOUS_HANDLER.store(previous as usize, Ordering::Relaxed);
+    }
+    Ok(reader)
+}
+
+/** Pass the size of the terminal on to the pty, if it changed. */
+fn resize(master: RawFd, size: &mut Winsize) {
+    if let Ok(new_size) = window_size() {
+        if new_size.ws_row != size.ws_row || new_size.ws_col != size.ws_col {
+            *size = new_size;
+            unsafe {
+                let _ = set_window_size(master, size);
+            }
+        }
+    }
+}
+
+fn is_readable(fd: &PollFd) -> bool {
+    fd.revents()
+        .map(|r| r.intersects(PollFlags::POLLIN | PollFlags::POLLHUP | PollFlags::POLLERR))
+        .unwrap_or(false)
+}
+
+fn write_all(fd: RawFd, mut buf: &[u8]) -> CrushResult<()> {
+    while !buf.is_empty() {
+        let written = to_crush_error(write(fd, buf))?;
+        buf = &buf[written..];
+    }
+    Ok(())
+}
+
+/** How a command that was forwarded to the terminal came back. */
+#[derive(Debug)]
+enum Outcome {
+    Exited(ExitStatus),
+    Stopped,
+}
+
+/** Poll for a change in the state of the command, also reporting it when it is stopped. */
+fn check(pid: Pid, flags: WaitPidFlag) -> CrushResult<Option<Outcome>> {
+    let status = to_crush_error(waitpid(pid, Some(flags | WaitPidFlag::WUNTRACED)))?;
+    Ok(match status {
+        WaitStatus::Exited(_, code) => Some(Outcome::Exited(ExitStatus::from_raw(code << 8))),
+        WaitStatus::Signaled(_, signal, core_dumped) => Some(Outcome::Exited(
+            ExitStatus::from_raw(signal as i32 | if core_dumped { 0x80 } else { 0 }),
+        )),
+        WaitStatus::Stopped(_, _) => Some(Outcome::Stopped),
+        _ => None,
+    })
+}
+
+/**
+  Run an external command on a pty of its own, connected to the terminal that Crush is running in.
+
+  The terminal is put in raw mode while the command runs, so every key press, including ^C and ^Z,
+  is passed on to the command as is. The line discipline of the pty then turns those into signals
+  for the command, rather than for the shell. When Crush gets SIGWINCH, the new size of the
+  terminal is passed on to the pty, which makes the kernel send SIGWINCH to the command too.
+
+  If the command is stopped, the terminal is restored and the command is added to the job table,
+  from where `fg` continues it attached to the terminal, and `bg` without it. The exit status is
+  only returned for a command that exits before being stopped.
+*/
+pub fn run(cmd: Command, printer: &Printer) -> CrushResult<Option<ExitStatus>> {
+    let source = once(cmd.get_program())
+        .chain(cmd.get_args())
+        .map(|a| a.to_string_lossy())
+        .collect::<Vec<_>>()
+        .join(" ");
+    let size = window_size()?;
+    let (master, pid) = spawn(cmd, &size)?;
+    attach(master, pid, size, source, printer)
+}
+
+/** Start a command with a new pty as its controlling terminal, returning the master side. */
+fn spawn(mut cmd: Command, size: &Winsize) -> CrushResult<(RawFd, Pid)> {
+    let pty = to_crush_error(openpty(size, None))?;
+    unsafe {
+        cmd.stdin(Stdio::from_raw_fd(to_crush_error(dup(pty.slave))?))
+            .stdout(Stdio::from_raw_fd(to_crush_error(dup(pty.slave))?))
+            .stderr(Stdio::from_raw_fd(to_crush_error(dup(pty.slave))?))
+            .pre_exec(|| {
+                // Start a new session, with the pty as its controlling terminal
+                setsid().map_err(|_| std::io::Error::last_os_error())?;
+                if libc::ioctl(STDIN, libc::TIOCSCTTY as _, 0) < 0 {
+                    return Err(std::io::Error::last_os_error());
+                }
+                Ok(())
+            });
+    }
+    let child = cmd.spawn();
+    // Close our copies of the slave, so that reading from the master fails once the command exits
+    drop(cmd);
+    let _ = close(pty.slave);
+    match to_crush_error(child) {
+        Ok(child) => Ok((pty.master, Pid::from_raw(child.id() as i32))),
+        Err(e) => {
+            let _ = close(pty.master);
+            Err(e)
+        }
+    }
+}
+
+/** Forward the terminal to a running command until it either exits or is stopped. */
+fn attach(
+    master: RawFd,
+    pid: Pid,
+    mut size: Winsize,
+    source: String,
+    printer: &Printer,
+) -> CrushResult<Option<ExitStatus>> {
+    let outcome = match std::io::stdout().into_raw_mode() {
+        // Dropping the raw terminal puts it back the way it was
+        Ok(mut stdout) => forward(master, STDIN, pid, &mut size, &mut stdout),
+        Err(e) => to_crush_error(Err(e)),
+    };
+    match outcome {
+        Ok(Outcome::Stopped) => {
+            suspend(master, pid, size, source, printer);
+            Ok(None)
+        }
+        Ok(Outcome::Exited(status)) => {
+            let _ = close(master);
+            Ok(Some(status))
+        }
+        Err(e) => {
+            let _ = close(master);
+            Err(e)
+        }
+    }
+}
+
+/** Add a stopped command to the job table, so that it can be continued later. */
+fn suspend(master: RawFd, pid: Pid, size: Winsize, source: String, printer: &Printer) {
+    let (job_printer, failed) = printer.tracked();
+    let foreground_printer = job_printer.clone();
+    let job_source = source.clone();
+    let stopped = Stopped {
+        foreground: Box::new(move || {
+            let res = to_crush_error(killpg(pid, Signal::SIGCONT))
+                .and_then(|_| attach(master, pid, size, job_source, &foreground_printer));
+            foreground_printer.handle_error(res);
+        }),
+        background: Box::new(move || {
+            to_crush_error(killpg(pid, Signal::SIGCONT))?;
+            Ok(handle(build("pty").spawn(move || {
+                job_printer.handle_error(copy_output(master, pid, &mut std::io::stdout()));
+                let _ = close(master);
+            })))
+        }),
+    };
+    let id = jobs().add(&source, JobJoinHandle::Stopped(stopped), failed);
+    printer.line(format_status(id, JobStatus::Stopped, &source).as_str());
+}
+
+/**
+  Copy the output of a command that was continued in the background until it exits. It gets no
+  input, and stopping it again has no effect on the job.
+*/
+fn copy_output(master: RawFd, pid: Pid, stdout: &mut impl Write) -> CrushResult<()> {
+    let mut buf = [0u8; 4096];
+    loop {
+        match read(master, &mut buf) {
+            Ok(0) | Err(_) => break,
+            Ok(n) => {
+                to_crush_error(stdout.write_all(&buf[..n]))?;
+                to_crush_error(stdout.flush())?;
+            }
+        }
+    }
+    to_crush_error(waitpid(pid, None))?;
+    Ok(())
+}
+
+/** Copy whatever output the command left behind when it exited, without waiting for more. */
+fn drain(master: RawFd, stdout: &mut impl Write, buf: &mut [u8]) -> CrushResult<()> {
+    loop {
+        let mut fds = [PollFd::new(master, PollFlags::POLLIN)];
+        if poll(&mut fds, 0).is_err() || !is_readable(&fds[0]) {
+            break;
+        }
+        match read(master, buf) {
+            Ok(0) | Err(_) => break,
+            Ok(n) => to_crush_error(stdout.write_all(&buf[..n]))?,
+        }
+    }
+    to_crush_error(stdout.flush())
+}
+
+fn forward(
+    master: RawFd,
+    input: RawFd,
+    pid: Pid,
+    size: &mut Winsize,
+    stdout: &mut impl Write,
+) -> CrushResult<Outcome> {
+    let window_changes = match &*WINDOW_CHANGES {
+        Ok(fd) => *fd,
+        Err(e) => return Err(e.clone()),
+    };
+    // The terminal may have changed size while the command was stopped
+    resize(master, size);
+    let mut input_open = true;
+    let mut buf = [0u8; 4096];
+    loop {
+        let mut fds = vec![
+            PollFd::new(master, PollFlags::POLLIN),
+            PollFd::new(window_changes, PollFlags::POLLIN),
+        ];
+        if input_open {
+            fds.push(PollFd::new(input, PollFlags::POLLIN));
+        }
+        // Time out now and then to notice that the command was stopped, which closes no file
+        match poll(&mut fds, 100) {
+            Ok(_) => {}
+            Err(e) if e.as_errno() == Some(Errno::EINTR) => continue,
+            Err(e) => return to_crush_error(Err(e)),
+        }
+
+        if is_readable(&fds[0]) {
+            match read(master, &mut buf) {
+                Ok(0) | Err(_) => break,
+                Ok(n) => {
+                    to_crush_error(stdout.write_all(&buf[..n]))?;
+                    to_crush_error(stdout.flush())?;
+                }
+            }
+        }
+
+        if is_readable(&fds[1]) {
+            while let Ok(n) = read(window_changes, &mut buf) {
+                if n == 0 {
+                    break;
+                }
+            }
+            resize(master, size);
+        }
+
+        if input_open && is_readable(&fds[2]) {
+            match read(input, &mut buf) {
+                Ok(0) | Err(_) => input_open = false,
+                Ok(n) => write_all(master, &buf[..n])?,
+            }
+        }
+
+        if let Some(outcome) = check(pid, WaitPidFlag::WNOHANG)? {
+            drain(master, stdout, &mut buf)?;
+            return Ok(outcome);
+        }
+    }
+    loop {
+        if let Some(outcome) = check(pid, WaitPidFlag::empty())? {
+            return Ok(outcome);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use nix::unistd::pipe;
+
+    const SIZE: Winsize = Winsize {
+        ws_row: 24,
+        ws_col: 80,
+        ws_xpixel: 0,
+        ws_ypixel: 0,
+    };
+
+    fn sh(script: &str) -> Command {
+        let mut cmd = Command::new("sh");
+        cmd.arg("-c").arg(script);
+        cmd
+    }
+
+    #[test]
+    fn forward_input_and_output() {
+        let (input, keyboard) = pipe().unwrap();
+        let (master, pid) = spawn(
+            sh("read line; sleep 0.2; echo \"got $line\"; exit 3"),
+            &SIZE,
+        )
+        .unwrap();
+        write_all(keyboard, b"hello\n").unwrap();
+        // Input that ends before the command does is no longer polled for
+        close(keyboard).unwrap();
+        let mut output = Vec::new();
+        let outcome = forward(master, input, pid, &mut SIZE.clone(), &mut output).unwrap();
+        let _ = close(master);
+        match outcome {
+            Outcome::Exited(status) => assert_eq!(status.code(), Some(3)),
+            Outcome::Stopped => panic!("Expected the command to exit"),
+        }
+        assert!(String::from_utf8_lossy(&output).contains("got hello"));
+    }
+
+    #[test]
+    fn forward_stops_with_command() {
+        let (input, _keyboard) = pipe().unwrap();
+        let (master, pid) = spawn(sh("kill -STOP $$; echo resumed"), &SIZE).unwrap();
+        let mut output = Vec::new();
+        let outcome = forward(master, input, pid, &mut SIZE.clone(), &mut output).unwrap();
+        assert!(matches!(outcome, Outcome::Stopped));
+
+        killpg(pid, Signal::SIGCONT).unwrap();
+        let outcome = forward(master, input, pid, &mut SIZE.clone(), &mut output).unwrap();
+        let _ = close(master);
+        assert!(matches!(outcome, Outcome::Exited(status) if status.success()));
+        assert!(String::from_utf8_lossy(&output).contains("resumed"));
+    }
+
+    #[test]
+    fn stopped_command_continues_without_input() {
+        let (input, _keyboard) = pipe().unwrap();
+        let (master, pid) = spawn(sh("kill -STOP $$; echo resumed"), &SIZE).unwrap();
+        let mut output = Vec::new();
+        let outcome = forward(master, input, pid, &mut SIZE.clone(), &mut output).unwrap();
+        assert!(matches!(outcome, Outcome::Stopped));
+
+        killpg(pid, Signal::SIGCONT).unwrap();
+        copy_output(master, pid, &mut output).unwrap();
+        let _ = close(master);
+        assert!(String::from_utf8_lossy(&output).contains("resumed"));
+    }
+}